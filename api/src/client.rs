@@ -0,0 +1,146 @@
+use crate::contestatie::Contestatie;
+use crate::county::{County, Participation, Unplaced};
+use crate::round::Round;
+use crate::school::FullSchool;
+use crate::status::Status;
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or its response could not be read.
+    Http(reqwest::Error),
+    /// The response was not the JSON envelope, e.g. a proxy error page.
+    Decode {
+        status: StatusCode,
+        source: serde_json::Error,
+    },
+    /// The server answered with an error message, e.g. for an unknown round.
+    Api(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "request failed: {err}"),
+            Error::Decode { status, source } => {
+                write!(f, "unexpected response ({status}): {source}")
+            }
+            Error::Api(message) => write!(f, "server error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(err) => Some(err),
+            Error::Decode { source, .. } => Some(source),
+            Error::Api(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Error {
+        Error::Http(err)
+    }
+}
+
+/// Client for the original API, one method per route.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+}
+
+impl Client {
+    /// A client for the API mounted at `base_url`, e.g. `http://localhost:8095/adm_api`.
+    ///
+    /// Panics if `base_url` cannot have a path, like `mailto:` URLs.
+    pub fn new(base_url: Url) -> Client {
+        Client::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Like [`Client::new`], sending requests through `http`, e.g. to set timeouts.
+    pub fn with_http_client(http: reqwest::Client, base_url: Url) -> Client {
+        assert!(
+            !base_url.cannot_be_a_base(),
+            "{base_url} cannot be a base URL"
+        );
+        Client { http, base_url }
+    }
+
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("checked in with_http_client")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn round_url(&self, segments: &[&str], round: Round) -> Url {
+        let mut url = self.url(segments);
+        url.query_pairs_mut()
+            .append_pair("round", &round.to_string());
+        url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T, Error> {
+        let response = self.http.get(url).send().await?;
+        let status = response.status();
+        let body = response.bytes().await?;
+        serde_json::from_slice::<Status<T>>(&body)
+            .map_err(|source| Error::Decode { status, source })?
+            .into_result()
+            .map_err(Error::Api)
+    }
+
+    /// Years with published results.
+    pub async fn years(&self) -> Result<Vec<i32>, Error> {
+        self.get(self.url(&["years"])).await
+    }
+
+    pub async fn counties(&self, year: i32) -> Result<Vec<County>, Error> {
+        self.get(self.url(&[&year.to_string(), "counties"])).await
+    }
+
+    /// Names of the high schools in `county` with seats in `round`.
+    pub async fn schools(
+        &self,
+        year: i32,
+        county: &str,
+        round: Round,
+    ) -> Result<Vec<String>, Error> {
+        self.get(self.round_url(&[&year.to_string(), county, "schools"], round))
+            .await
+    }
+
+    /// Specializations of a high school and the candidates placed in each.
+    pub async fn full_school(
+        &self,
+        year: i32,
+        county: &str,
+        school: &str,
+        round: Round,
+    ) -> Result<FullSchool, Error> {
+        self.get(self.round_url(&[&year.to_string(), county, "fullSchool", school], round))
+            .await
+    }
+
+    pub async fn unplaced(&self, year: i32, county: &str, round: Round) -> Result<Unplaced, Error> {
+        self.get(self.round_url(&[&year.to_string(), county, "unplaced"], round))
+            .await
+    }
+
+    pub async fn participation(&self, year: i32, county: &str) -> Result<Participation, Error> {
+        self.get(self.url(&[&year.to_string(), county, "participation"]))
+            .await
+    }
+
+    pub async fn appeals(&self, year: i32, county: &str) -> Result<Vec<Contestatie>, Error> {
+        self.get(self.url(&[&year.to_string(), county, "appeals"]))
+            .await
+    }
+}
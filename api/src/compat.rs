@@ -0,0 +1,52 @@
+use std::cell::Cell;
+
+thread_local! {
+    static LEGACY_SENTINELS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` with missing values serialized as `-1` instead of `null` if `enabled`, for clients
+/// that predate nullable fields. Only serialization on the current thread, inside `f`, is
+/// affected, so a server can pick the rendering per request.
+pub fn with_legacy_sentinels<R>(enabled: bool, f: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LEGACY_SENTINELS.with(|legacy| legacy.set(self.0));
+        }
+    }
+
+    let _restore = Restore(LEGACY_SENTINELS.with(|legacy| legacy.replace(enabled)));
+    f()
+}
+
+pub fn legacy_sentinels() -> bool {
+    LEGACY_SENTINELS.with(Cell::get)
+}
+
+/// `serde(with)` module for optional values, written as `null` or `-1` depending on
+/// [`legacy_sentinels`] and read back as `None` from either.
+pub mod sentinel {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + From<i8>,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None if super::legacy_sentinels() => T::from(-1).serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Deserialize<'de> + PartialEq + From<i8>,
+        D: Deserializer<'de>,
+    {
+        let value = Option::<T>::deserialize(deserializer)?;
+        Ok(value.filter(|value| *value != T::from(-1)))
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// A candidate whose grades changed after appeal, with the admission average
+/// and county rank they would have had without it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Contestatie {
+    pub id: String,
+    pub judet: String,
+
+    pub nota_ro_initiala: Option<f64>,
+    pub nota_ro: Option<f64>,
+    pub nota_mate_initiala: Option<f64>,
+    pub nota_mate: Option<f64>,
+    pub nota_lm_initiala: Option<f64>,
+    pub nota_lm: Option<f64>,
+
+    pub medie_en_initiala: Option<f64>,
+    pub medie_en: Option<f64>,
+    #[serde(rename = "medie_adm_initiala")]
+    pub medie_admitere_initiala: Option<f64>,
+    #[serde(rename = "medie_adm")]
+    pub medie_admitere: Option<f64>,
+
+    pub loc_initial: Option<usize>,
+    pub loc: Option<usize>,
+}
+
+impl Contestatie {
+    pub fn improved(&self) -> bool {
+        matches!((self.loc_initial, self.loc), (Some(initial), Some(fin)) if fin < initial)
+    }
+}
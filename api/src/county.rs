@@ -0,0 +1,42 @@
+use crate::student::Student;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct County {
+    /// SIRUTA code, `None` for a code missing from the official list.
+    #[serde(with = "crate::compat::sentinel")]
+    pub id: Option<i32>,
+    pub code: String,
+    pub name: String,
+}
+
+/// Number of candidates whose admission average falls in `[grade, grade + 1)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct GradeBucket {
+    pub grade: i32,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Unplaced {
+    pub elevi: Vec<Student>,
+    #[serde(rename = "distributie")]
+    pub distribution: Vec<GradeBucket>,
+}
+
+/// How many candidates sat the Evaluare Națională and how many went on to the repartizare.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Participation {
+    pub inscrisi: i64,
+    pub prezenti: i64,
+    pub absenti: i64,
+    pub contestatii: i64,
+    pub admitere: i64,
+}
@@ -0,0 +1,20 @@
+#[cfg(feature = "client")]
+pub mod client;
+pub mod compat;
+pub mod contestatie;
+pub mod county;
+pub mod round;
+pub mod school;
+pub mod specializare;
+pub mod status;
+pub mod student;
+
+#[cfg(feature = "client")]
+pub use client::{Client, Error};
+pub use contestatie::Contestatie;
+pub use county::{County, GradeBucket, Participation, Unplaced};
+pub use round::Round;
+pub use school::{FullSchool, FullSpec, SpecShort};
+pub use specializare::Specializare;
+pub use status::{Status, StatusData};
+pub use student::{Placement, Student};
@@ -0,0 +1,97 @@
+use crate::student::Placement;
+
+/// A stage of the repartizare, each published by the ministry under its own path.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[repr(i32)]
+pub enum Round {
+    /// The first computerized repartizare.
+    #[default]
+    First = 1,
+    /// The second stage, which refills the seats left after the first round.
+    Second = 2,
+    /// Seats reserved for special-needs and Roma candidates.
+    Special = 3,
+}
+
+impl Round {
+    pub const ALL: [Round; 3] = [Round::First, Round::Second, Round::Special];
+
+    /// Status of a candidate who got a seat in this round.
+    pub fn placement(self) -> Placement {
+        match self {
+            Round::First => Placement::Placed,
+            Round::Second => Placement::Redistributed,
+            Round::Special => Placement::SpecialSession,
+        }
+    }
+}
+
+#[cfg(feature = "utoipa")]
+impl<'s> utoipa::ToSchema<'s> for Round {
+    fn schema() -> (
+        &'s str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        use utoipa::openapi::{ObjectBuilder, SchemaType};
+
+        (
+            "Round",
+            ObjectBuilder::new()
+                .schema_type(SchemaType::Integer)
+                .enum_values(Some(Round::ALL.map(|round| round as i32)))
+                .description(Some(
+                    "1 for the first repartizare, 2 for the second, 3 for the seats reserved for special-needs and Roma candidates",
+                ))
+                .into(),
+        )
+    }
+}
+
+impl TryFrom<i32> for Round {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Round::ALL
+            .into_iter()
+            .find(|round| *round as i32 == value)
+            .ok_or(format!("Unknown round {value}"))
+    }
+}
+
+impl std::str::FromStr for Round {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| format!("Unknown round {value}"))
+            .and_then(Round::try_from)
+    }
+}
+
+impl std::fmt::Display for Round {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", *self as i32)
+    }
+}
+
+impl serde::Serialize for Round {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_i32(*self as i32)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Round {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = i32::deserialize(deserializer)?;
+        Round::try_from(value).map_err(serde::de::Error::custom)
+    }
+}
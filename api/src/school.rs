@@ -0,0 +1,29 @@
+use crate::specializare::Specializare;
+use crate::student::Student;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct SpecShort {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FullSpec {
+    pub elevi: Vec<Student>,
+    #[serde(rename = "sp")]
+    pub spec: Specializare,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FullSchool {
+    #[serde(rename = "specs")]
+    pub specializari_short: Vec<SpecShort>,
+    #[serde(rename = "spec_data")]
+    pub specializari: HashMap<i32, FullSpec>,
+}
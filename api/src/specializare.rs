@@ -0,0 +1,34 @@
+use crate::round::Round;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Specializare {
+    pub id: i32,
+    pub round: Round,
+
+    pub name: String,
+    pub judet: String,
+
+    pub liceu: String,
+    pub mediu: String,
+
+    pub specializare: String,
+    pub bilingv: bool,
+
+    #[serde(with = "crate::compat::sentinel")]
+    pub locuri: Option<i32>,
+    #[serde(with = "crate::compat::sentinel")]
+    pub ocupate: Option<i32>,
+
+    pub profil: String,
+    pub filiera: String,
+
+    #[serde(with = "crate::compat::sentinel")]
+    pub ultima_medie: Option<f64>,
+    #[serde(rename = "ultima_medie_ant")]
+    #[serde(with = "crate::compat::sentinel")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "ultima_medie_ant"))]
+    pub ultima_medie_anterior: Option<f64>,
+}
@@ -0,0 +1,91 @@
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, MapAccess, Visitor};
+use serde::Serialize;
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum StatusData<T> {
+    Success(T),
+    Error(String),
+}
+
+/// The envelope around every response: `type` is `success` or `error`, and for errors `data`
+/// is a message.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status<T> {
+    #[serde(rename = "type")]
+    pub result_type: String,
+    pub data: StatusData<T>,
+}
+
+impl<T> Status<T> {
+    pub fn success(data: T) -> Status<T> {
+        Status {
+            result_type: "success".to_string(),
+            data: StatusData::Success(data),
+        }
+    }
+
+    pub fn error(message: String) -> Status<T> {
+        Status {
+            result_type: "error".to_string(),
+            data: StatusData::Error(message),
+        }
+    }
+
+    /// The data, or the error message the server sent instead.
+    pub fn into_result(self) -> Result<T, String> {
+        match self.data {
+            StatusData::Success(data) => Ok(data),
+            StatusData::Error(message) => Err(message),
+        }
+    }
+}
+
+// `data` is read as the variant named by `type`, which the server always writes first. An
+// untagged derive would buffer it instead, and buffered maps can't have integer keys.
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Status<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct StatusVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: DeserializeOwned> Visitor<'de> for StatusVisitor<T> {
+            type Value = Status<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a response with `type` followed by `data`")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut result_type: Option<String> = None;
+                let mut data = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "type" => result_type = Some(map.next_value()?),
+                        "data" => {
+                            data = Some(match result_type.as_deref() {
+                                Some("error") => StatusData::Error(map.next_value()?),
+                                Some(_) => StatusData::Success(map.next_value()?),
+                                None => return Err(de::Error::custom("`data` before `type`")),
+                            })
+                        }
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(Status {
+                    result_type: result_type.ok_or_else(|| de::Error::missing_field("type"))?,
+                    data: data.ok_or_else(|| de::Error::missing_field("data"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(StatusVisitor(PhantomData))
+    }
+}
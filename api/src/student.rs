@@ -0,0 +1,109 @@
+use crate::round::Round;
+use serde::{Deserialize, Serialize};
+
+/// Where a candidate ended up after the repartizare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "sqlx",
+    derive(sqlx::Type),
+    sqlx(type_name = "TEXT", rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Placement {
+    Placed,
+    Unplaced,
+    Redistributed,
+    SpecialSession,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Student {
+    pub id: String,
+    pub round: Round,
+    pub provenienta: String,
+    pub judet: String,
+
+    #[serde(rename = "medie_adm")]
+    #[serde(with = "crate::compat::sentinel")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "medie_adm"))]
+    pub medie_admitere: Option<f64>,
+    #[serde(rename = "medie_en")]
+    #[serde(with = "crate::compat::sentinel")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "medie_en"))]
+    pub medie_evaluare: Option<f64>,
+    #[serde(rename = "medie_abs")]
+    #[serde(with = "crate::compat::sentinel")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "medie_abs"))]
+    pub medie_absolvire: Option<f64>,
+
+    #[serde(rename = "nota_ro")]
+    #[serde(with = "crate::compat::sentinel")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "nota_ro"))]
+    pub nota_romana: Option<f64>,
+    #[serde(rename = "nota_mate")]
+    #[serde(with = "crate::compat::sentinel")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "nota_mate"))]
+    pub nota_mate: Option<f64>,
+
+    pub liceu: String,
+    pub status: Placement,
+    #[serde(with = "crate::compat::sentinel")]
+    pub id_specializare: Option<i32>,
+    #[serde(rename = "specializare_display")]
+    #[cfg_attr(feature = "sqlx", sqlx(rename = "specializare_display"))]
+    pub specializare: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat::with_legacy_sentinels;
+
+    fn unplaced() -> Student {
+        Student {
+            id: "AB5".to_string(),
+            round: Round::First,
+            provenienta: "Scoala".to_string(),
+            judet: "AB".to_string(),
+            medie_admitere: Some(5.2),
+            medie_evaluare: None,
+            medie_absolvire: Some(8.0),
+            nota_romana: None,
+            nota_mate: None,
+            liceu: String::new(),
+            status: Placement::Unplaced,
+            id_specializare: None,
+            specializare: String::new(),
+        }
+    }
+
+    #[test]
+    fn missing_values_are_null() {
+        let json = serde_json::to_value(unplaced()).unwrap();
+
+        assert!(json["id_specializare"].is_null());
+        assert!(json["medie_en"].is_null());
+    }
+
+    #[test]
+    fn legacy_sentinels_cover_every_missing_value() {
+        let json = with_legacy_sentinels(true, || serde_json::to_value(unplaced()).unwrap());
+
+        assert_eq!(json["id_specializare"], -1);
+        assert_eq!(json["medie_en"], -1.0);
+        assert_eq!(json["nota_ro"], -1.0);
+        assert_eq!(json["medie_adm"], 5.2);
+    }
+
+    #[test]
+    fn sentinels_read_back_as_missing() {
+        let json = with_legacy_sentinels(true, || serde_json::to_string(&unplaced()).unwrap());
+        let student: Student = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(student.id_specializare, None);
+        assert_eq!(student.medie_evaluare, None);
+    }
+}
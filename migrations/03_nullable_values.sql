@@ -0,0 +1,58 @@
+CREATE TABLE specializari_new (
+    id 			INTEGER NOT NULL,
+    name 		TEXT 	NOT NULL,
+    liceu 		TEXT 	NOT NULL,
+    mediu 		TEXT 	NOT NULL,
+    judet 		TEXT    NOT NULL REFERENCES counties(code),
+
+    specializare TEXT 	NOT NULL,
+    bilingv 	INTEGER NOT NULL,
+
+    locuri 		INTEGER,
+    ocupate 	INTEGER,
+
+    profil 		TEXT 	NOT NULL,
+    filiera 	TEXT 	NOT NULL,
+
+    ultima_medie REAL,
+    ultima_medie_ant REAL
+);
+
+INSERT INTO specializari_new
+SELECT
+    id, name, liceu, mediu, judet, specializare, bilingv,
+    NULLIF(locuri, -1), NULLIF(ocupate, -1),
+    profil, filiera,
+    NULLIF(ultima_medie, -1.0), NULLIF(ultima_medie_ant, -1.0)
+FROM specializari;
+
+DROP TABLE specializari;
+ALTER TABLE specializari_new RENAME TO specializari;
+
+CREATE TABLE students_new (
+    id 			TEXT 	NOT NULL,
+    provenienta TEXT 	NOT NULL,
+    judet 		TEXT    NOT NULL REFERENCES counties(code),
+    
+    medie_adm   REAL,
+    medie_en 	REAL,
+    medie_abs 	REAL,
+
+    nota_ro 	 REAL 	NOT NULL,
+    nota_mate 	 REAL 	NOT NULL,
+
+    liceu 					TEXT 	NOT NULL,
+    id_specializare 		INTEGER NOT NULL,
+    specializare_display 	TEXT NOT NULL
+);
+
+INSERT INTO students_new
+SELECT
+    id, provenienta, judet,
+    NULLIF(medie_adm, -1.0), NULLIF(medie_en, -1.0), NULLIF(medie_abs, -1.0),
+    nota_ro, nota_mate,
+    liceu, id_specializare, specializare_display
+FROM students;
+
+DROP TABLE students;
+ALTER TABLE students_new RENAME TO students;
@@ -0,0 +1,33 @@
+CREATE TABLE students_new (
+    id 			TEXT 	NOT NULL,
+    provenienta TEXT 	NOT NULL,
+    judet 		TEXT    NOT NULL REFERENCES counties(code),
+    
+    medie_adm   REAL,
+    medie_en 	REAL,
+    medie_abs 	REAL,
+
+    nota_ro 	 REAL 	NOT NULL,
+    nota_mate 	 REAL 	NOT NULL,
+
+    liceu 					TEXT 	NOT NULL,
+    status 					TEXT 	NOT NULL DEFAULT 'placed',
+    id_specializare 		INTEGER,
+    specializare_display 	TEXT NOT NULL
+);
+
+INSERT INTO students_new
+SELECT
+    id, provenienta, judet,
+    medie_adm, medie_en, medie_abs,
+    nota_ro, nota_mate,
+    liceu,
+    CASE WHEN specializare_display = 'Nerepartizat' THEN 'unplaced' ELSE 'placed' END,
+    CASE WHEN specializare_display = 'Nerepartizat' THEN NULL ELSE id_specializare END,
+    specializare_display
+FROM students;
+
+DROP TABLE students;
+ALTER TABLE students_new RENAME TO students;
+
+DELETE FROM specializari WHERE id <= 0 AND name LIKE 'Nerepartizat %';
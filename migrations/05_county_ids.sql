@@ -0,0 +1,48 @@
+ALTER TABLE counties ADD COLUMN id INTEGER;
+
+UPDATE counties SET id = CASE UPPER(code)
+    WHEN 'AB' THEN 1
+    WHEN 'AR' THEN 2
+    WHEN 'AG' THEN 3
+    WHEN 'BC' THEN 4
+    WHEN 'BH' THEN 5
+    WHEN 'BN' THEN 6
+    WHEN 'BT' THEN 7
+    WHEN 'BV' THEN 8
+    WHEN 'BR' THEN 9
+    WHEN 'BZ' THEN 10
+    WHEN 'CS' THEN 11
+    WHEN 'CJ' THEN 12
+    WHEN 'CT' THEN 13
+    WHEN 'CV' THEN 14
+    WHEN 'DB' THEN 15
+    WHEN 'DJ' THEN 16
+    WHEN 'GL' THEN 17
+    WHEN 'GJ' THEN 18
+    WHEN 'HR' THEN 19
+    WHEN 'HD' THEN 20
+    WHEN 'IL' THEN 21
+    WHEN 'IS' THEN 22
+    WHEN 'IF' THEN 23
+    WHEN 'MM' THEN 24
+    WHEN 'MH' THEN 25
+    WHEN 'MS' THEN 26
+    WHEN 'NT' THEN 27
+    WHEN 'OT' THEN 28
+    WHEN 'PH' THEN 29
+    WHEN 'SM' THEN 30
+    WHEN 'SJ' THEN 31
+    WHEN 'SB' THEN 32
+    WHEN 'SV' THEN 33
+    WHEN 'TR' THEN 34
+    WHEN 'TM' THEN 35
+    WHEN 'TL' THEN 36
+    WHEN 'VS' THEN 37
+    WHEN 'VL' THEN 38
+    WHEN 'VN' THEN 39
+    WHEN 'B' THEN 40
+    WHEN 'CL' THEN 51
+    WHEN 'GR' THEN 52
+END;
+
+CREATE UNIQUE INDEX IF NOT EXISTS counties_id ON counties(id);
@@ -0,0 +1,5 @@
+ALTER TABLE specializari ADD COLUMN round INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE students ADD COLUMN round INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS specializari_round ON specializari(judet, round);
+CREATE INDEX IF NOT EXISTS students_round ON students(judet, round);
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS evaluare (
+    id 			TEXT 	NOT NULL,
+    judet 		TEXT    NOT NULL REFERENCES counties(code),
+    scoala 		TEXT 	NOT NULL,
+
+    nota_ro_initiala 		REAL,
+    nota_ro_contestatie 	REAL,
+    nota_ro 				REAL,
+
+    nota_mate_initiala 		REAL,
+    nota_mate_contestatie 	REAL,
+    nota_mate 				REAL,
+
+    limba_materna 			TEXT,
+    nota_lm_initiala 		REAL,
+    nota_lm_contestatie 	REAL,
+    nota_lm 				REAL,
+
+    medie 		REAL
+);
+
+CREATE INDEX IF NOT EXISTS evaluare_id ON evaluare(id);
+CREATE INDEX IF NOT EXISTS evaluare_judet ON evaluare(judet);
+CREATE INDEX IF NOT EXISTS students_id ON students(id);
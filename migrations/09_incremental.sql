@@ -0,0 +1,20 @@
+DELETE FROM specializari WHERE rowid NOT IN (
+    SELECT MIN(rowid) FROM specializari GROUP BY judet, round, id
+);
+DELETE FROM students WHERE rowid NOT IN (
+    SELECT MIN(rowid) FROM students GROUP BY judet, round, id
+);
+DELETE FROM evaluare WHERE rowid NOT IN (
+    SELECT MIN(rowid) FROM evaluare GROUP BY judet, id
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS specializari_key ON specializari(judet, round, id);
+CREATE UNIQUE INDEX IF NOT EXISTS students_key ON students(judet, round, id);
+CREATE UNIQUE INDEX IF NOT EXISTS evaluare_key ON evaluare(judet, id);
+
+CREATE TABLE IF NOT EXISTS sources (
+    url 			TEXT PRIMARY KEY,
+    etag 			TEXT,
+    last_modified 	TEXT,
+    hash 			TEXT NOT NULL
+);
@@ -0,0 +1,55 @@
+DROP VIEW IF EXISTS contestatii;
+
+CREATE TABLE students_new (
+    id 			TEXT 	NOT NULL,
+    provenienta TEXT 	NOT NULL,
+    judet 		TEXT    NOT NULL REFERENCES counties(code),
+    
+    medie_adm   REAL,
+    medie_en 	REAL,
+    medie_abs 	REAL,
+
+    nota_ro 	 REAL,
+    nota_mate 	 REAL,
+
+    liceu 					TEXT 	NOT NULL,
+    status 					TEXT 	NOT NULL DEFAULT 'placed',
+    id_specializare 		INTEGER,
+    specializare_display 	TEXT NOT NULL,
+    round 					INTEGER NOT NULL DEFAULT 1
+);
+
+INSERT INTO students_new
+SELECT
+    id, provenienta, judet,
+    medie_adm, medie_en, medie_abs,
+    NULLIF(nota_ro, -1.0), NULLIF(nota_mate, -1.0),
+    liceu, status, id_specializare, specializare_display, round
+FROM students;
+
+DROP TABLE students;
+ALTER TABLE students_new RENAME TO students;
+
+CREATE INDEX IF NOT EXISTS students_round ON students(judet, round);
+CREATE INDEX IF NOT EXISTS students_id ON students(id);
+CREATE UNIQUE INDEX IF NOT EXISTS students_key ON students(judet, round, id);
+
+CREATE VIEW IF NOT EXISTS contestatii AS
+SELECT
+    s.id AS id,
+    s.judet AS judet,
+
+    e.nota_ro_initiala AS nota_ro_initiala,
+    e.nota_ro AS nota_ro,
+    e.nota_mate_initiala AS nota_mate_initiala,
+    e.nota_mate AS nota_mate,
+    e.nota_lm_initiala AS nota_lm_initiala,
+    e.nota_lm AS nota_lm,
+
+    s.medie_en AS medie_en,
+    s.medie_adm AS medie_adm
+FROM evaluare e
+JOIN students s ON s.id = e.id AND s.round = 1
+WHERE e.nota_ro_initiala IS NOT e.nota_ro
+    OR e.nota_mate_initiala IS NOT e.nota_mate
+    OR e.nota_lm_initiala IS NOT e.nota_lm;
@@ -0,0 +1,21 @@
+DROP VIEW IF EXISTS contestatii;
+
+CREATE VIEW contestatii AS
+SELECT
+    s.id AS id,
+    s.judet AS judet,
+
+    e.nota_ro_initiala AS nota_ro_initiala,
+    e.nota_ro AS nota_ro,
+    e.nota_mate_initiala AS nota_mate_initiala,
+    e.nota_mate AS nota_mate,
+    e.nota_lm_initiala AS nota_lm_initiala,
+    e.nota_lm AS nota_lm,
+
+    s.medie_en AS medie_en,
+    s.medie_adm AS medie_adm
+FROM evaluare e
+JOIN students s ON s.judet = e.judet AND s.id = e.id AND s.round = 1
+WHERE e.nota_ro_initiala IS NOT e.nota_ro
+    OR e.nota_mate_initiala IS NOT e.nota_mate
+    OR e.nota_lm_initiala IS NOT e.nota_lm;
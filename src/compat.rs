@@ -0,0 +1,53 @@
+use crate::config::ServerConfig;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Polls `inner` with the chosen rendering of missing values, so it applies to the response
+/// serialized by the handler and to nothing else running on the same thread.
+struct WithSentinels<F> {
+    legacy: bool,
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for WithSentinels<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let legacy = self.legacy;
+        repartizare_api::compat::with_legacy_sentinels(legacy, || self.inner.as_mut().poll(cx))
+    }
+}
+
+/// Whether the request asked for `-1` (`?sentinels=legacy`) or `null` (`?sentinels=null`) for
+/// missing values, if it chose at all.
+fn requested<B>(req: &Request<B>) -> Option<bool> {
+    let query = req.uri().query()?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("sentinels="))
+        .and_then(|val| match val {
+            "legacy" => Some(true),
+            "null" => Some(false),
+            _ => None,
+        })
+}
+
+/// Render missing values the way the request asked, or as configured by `legacy_sentinels`, so
+/// old and new clients of the original API can be served side by side.
+pub async fn sentinels<B: Send>(req: Request<B>, next: Next<B>) -> Response {
+    let legacy = requested(&req).unwrap_or_else(|| {
+        req.extensions()
+            .get::<Arc<ServerConfig>>()
+            .is_some_and(|config| config.legacy_sentinels)
+    });
+    WithSentinels {
+        legacy,
+        inner: Box::pin(next.run(req)),
+    }
+    .await
+}
@@ -0,0 +1,263 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Prefix of the environment variables that override the config file, e.g. `ADM_BIND`.
+const ENV_PREFIX: &str = "ADM_";
+
+/// Settings for `server`, read from a TOML file and then overridden by `ADM_*` environment
+/// variables and command line flags, in that order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Where the API is mounted, e.g. `/adm_api`. Empty or `/` mounts it at the root.
+    pub base_path: String,
+    /// Where the second version of the API is mounted.
+    pub v2_base_path: String,
+    /// Directory holding one `{year}.db` per year.
+    pub data_dir: PathBuf,
+    /// Connections kept open per year database.
+    pub pool_size: u32,
+    /// Origins allowed to call the API from a browser. Empty disables CORS.
+    pub cors_origins: Vec<String>,
+    pub cache: CacheConfig,
+    pub rate_limit: RateLimitConfig,
+    /// Years listed by `/years`.
+    pub years: Vec<i32>,
+    /// Serialize missing values as -1 instead of null, like older releases did. A request to the
+    /// original API can pick either with `?sentinels=legacy` or `?sentinels=null`.
+    pub legacy_sentinels: bool,
+    /// Seconds in-flight requests get to finish after SIGTERM or Ctrl-C.
+    pub shutdown_timeout: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Seconds clients and proxies may reuse a response, sent as `Cache-Control: max-age`.
+    pub max_age: u64,
+    /// `max-age` for years older than the newest one in `years`, whose results no longer change.
+    pub past_years_max_age: u64,
+    /// Responses kept in memory. 0 disables the in-memory cache.
+    pub entries: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            bind: SocketAddr::from(([0, 0, 0, 0], 8095)),
+            base_path: "/adm_api".to_string(),
+            v2_base_path: "/api/v2".to_string(),
+            data_dir: PathBuf::from("./"),
+            pool_size: crate::db::DEFAULT_POOL_SIZE,
+            cors_origins: Vec::new(),
+            cache: CacheConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            years: vec![2020, 2021, 2022, 2023],
+            legacy_sentinels: false,
+            shutdown_timeout: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Proxies, as addresses or CIDR ranges, whose `X-Forwarded-For` is believed.
+    pub trusted_proxies: Vec<String>,
+    /// Limit for routes not listed in `routes`, shared between all of them.
+    pub default: Limit,
+    /// Limits for single routes, keyed by name: `years`, `counties`, `schools`, `fullSchool`,
+    /// `unplaced`, `participation` or `appeals`.
+    pub routes: HashMap<String, Limit>,
+}
+
+/// A token bucket allowing `burst` requests at once, refilled at `per_second`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: false,
+            trusted_proxies: Vec::new(),
+            default: Limit {
+                per_second: 10.0,
+                burst: 30,
+            },
+            routes: HashMap::from([(
+                "fullSchool".to_string(),
+                Limit {
+                    per_second: 1.0,
+                    burst: 10,
+                },
+            )]),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig {
+            max_age: 60,
+            past_years_max_age: 86400,
+            entries: 1024,
+        }
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(format!("{ENV_PREFIX}{name}"))
+        .ok()
+        .filter(|x| !x.is_empty())
+}
+
+fn parse_env<T: FromStr>(name: &str) -> Result<Option<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    env(name)
+        .map(|val| {
+            val.parse()
+                .map_err(|err| format!("{ENV_PREFIX}{name}={val}: {err}"))
+        })
+        .transpose()
+}
+
+fn parse_env_list<T: FromStr>(name: &str) -> Result<Option<Vec<T>>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    env(name)
+        .map(|val| {
+            val.split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(|x| {
+                    x.parse()
+                        .map_err(|err| format!("{ENV_PREFIX}{name}={val}: {err}"))
+                })
+                .collect()
+        })
+        .transpose()
+}
+
+impl ServerConfig {
+    /// Read `path` if given, then apply the environment overrides.
+    pub fn load(path: Option<&Path>) -> Result<ServerConfig, Box<dyn std::error::Error>> {
+        let mut config = match path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|err| format!("{}: {err}", path.display()))?;
+                toml::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))?
+            }
+            None => ServerConfig::default(),
+        };
+
+        if let Some(bind) = parse_env("BIND")? {
+            config.bind = bind;
+        }
+        if let Some(base_path) = env("BASE_PATH") {
+            config.base_path = base_path;
+        }
+        if let Some(base_path) = env("V2_BASE_PATH") {
+            config.v2_base_path = base_path;
+        }
+        if let Some(data_dir) = env("DATA_DIR") {
+            config.data_dir = PathBuf::from(data_dir);
+        }
+        if let Some(pool_size) = parse_env("POOL_SIZE")? {
+            config.pool_size = pool_size;
+        }
+        if let Some(origins) = parse_env_list("CORS_ORIGINS")? {
+            config.cors_origins = origins;
+        }
+        if let Some(max_age) = parse_env("CACHE_MAX_AGE")? {
+            config.cache.max_age = max_age;
+        }
+        if let Some(max_age) = parse_env("CACHE_PAST_YEARS_MAX_AGE")? {
+            config.cache.past_years_max_age = max_age;
+        }
+        if let Some(entries) = parse_env("CACHE_ENTRIES")? {
+            config.cache.entries = entries;
+        }
+        if let Some(years) = parse_env_list("YEARS")? {
+            config.years = years;
+        }
+        if let Some(legacy_sentinels) = parse_env("LEGACY_SENTINELS")? {
+            config.legacy_sentinels = legacy_sentinels;
+        }
+
+        if let Some(enabled) = parse_env("RATE_LIMIT")? {
+            config.rate_limit.enabled = enabled;
+        }
+        if let Some(proxies) = parse_env_list("TRUSTED_PROXIES")? {
+            config.rate_limit.trusted_proxies = proxies;
+        }
+        if let Some(timeout) = parse_env("SHUTDOWN_TIMEOUT")? {
+            config.shutdown_timeout = timeout;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.pool_size == 0 {
+            return Err("pool_size must be at least 1".to_string());
+        }
+        if !self.base_path.is_empty() && !self.base_path.starts_with('/') {
+            return Err(format!("base_path {} must start with /", self.base_path));
+        }
+        if !self.v2_base_path.starts_with('/') || self.v2_base_path() == self.base_path() {
+            return Err(format!(
+                "v2_base_path {} must start with / and differ from base_path",
+                self.v2_base_path
+            ));
+        }
+        if self.v2_base_path().is_empty() {
+            return Err("v2_base_path cannot be the root".to_string());
+        }
+        let limits = std::iter::once(("default", &self.rate_limit.default)).chain(
+            self.rate_limit
+                .routes
+                .iter()
+                .map(|(route, limit)| (route.as_str(), limit)),
+        );
+        for (route, limit) in limits {
+            if limit.per_second.is_nan() || limit.per_second <= 0.0 || limit.burst == 0 {
+                return Err(format!(
+                    "rate limit for {route} needs a positive per_second and burst"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// `Cache-Control` value for responses built from `year`'s database.
+    pub fn cache_control(&self, year: Option<i32>) -> String {
+        let latest = self.years.iter().copied().max();
+        let max_age = match (year, latest) {
+            (Some(year), Some(latest)) if year < latest => self.cache.past_years_max_age,
+            _ => self.cache.max_age,
+        };
+        format!("public, max-age={max_age}")
+    }
+
+    /// The base path without a trailing slash, empty when the API is mounted at the root.
+    pub fn base_path(&self) -> &str {
+        self.base_path.trim_end_matches('/')
+    }
+
+    pub fn v2_base_path(&self) -> &str {
+        self.v2_base_path.trim_end_matches('/')
+    }
+}
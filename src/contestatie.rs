@@ -0,0 +1,260 @@
+use crate::db;
+
+pub use repartizare_api::Contestatie;
+
+/// Share of the Evaluare Națională average in the admission average of `year`, the rest being the
+/// graduation average: 80% since 2021, 75% before.
+fn en_weight(year: i32) -> f64 {
+    if year >= 2021 {
+        0.8
+    } else {
+        0.75
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ContestatieRow {
+    id: String,
+    judet: String,
+
+    nota_ro_initiala: Option<f64>,
+    nota_ro: Option<f64>,
+    nota_mate_initiala: Option<f64>,
+    nota_mate: Option<f64>,
+    nota_lm_initiala: Option<f64>,
+    nota_lm: Option<f64>,
+
+    medie_en: Option<f64>,
+    medie_adm: Option<f64>,
+}
+
+fn from_row(row: ContestatieRow, year: i32, medii: &[f64]) -> Contestatie {
+    let mut grades = vec![row.nota_ro_initiala, row.nota_mate_initiala];
+    if row.nota_lm.is_some() {
+        grades.push(row.nota_lm_initiala);
+    }
+    let medie_en_initiala = grades
+        .iter()
+        .copied()
+        .sum::<Option<f64>>()
+        .map(|sum| sum / grades.len() as f64);
+
+    let medie_admitere_initiala = match (row.medie_adm, row.medie_en, medie_en_initiala) {
+        (Some(adm), Some(en), Some(en_initiala)) => {
+            Some(adm - en_weight(year) * (en - en_initiala))
+        }
+        _ => None,
+    };
+
+    Contestatie {
+        id: row.id,
+        judet: row.judet,
+        nota_ro_initiala: row.nota_ro_initiala,
+        nota_ro: row.nota_ro,
+        nota_mate_initiala: row.nota_mate_initiala,
+        nota_mate: row.nota_mate,
+        nota_lm_initiala: row.nota_lm_initiala,
+        nota_lm: row.nota_lm,
+        medie_en_initiala,
+        medie_en: row.medie_en,
+        medie_admitere_initiala,
+        medie_admitere: row.medie_adm,
+        loc_initial: medie_admitere_initiala.map(|medie| {
+            // `medii` holds this candidate's final average, which is not a competitor
+            // for the rank they would have had without the appeal
+            let own = row.medie_adm.is_some_and(|adm| adm > medie);
+            rank(medii, medie) - own as usize
+        }),
+        loc: row.medie_adm.map(|medie| rank(medii, medie)),
+    }
+}
+
+/// 1-based position of `medie` among `medii`, sorted in descending order.
+fn rank(medii: &[f64], medie: f64) -> usize {
+    medii.partition_point(|x| *x > medie) + 1
+}
+
+pub async fn for_county(
+    db: &sqlx::SqlitePool,
+    year: i32,
+    county: &str,
+) -> Result<Vec<Contestatie>, sqlx::Error> {
+    let mut medii = sqlx::query_scalar::<_, f64>(
+        "SELECT medie_adm FROM students WHERE judet = ? AND round = 1 AND medie_adm IS NOT NULL",
+    )
+    .bind(county)
+    .fetch_all(db)
+    .await?;
+    medii.sort_by(|a, b| b.total_cmp(a));
+
+    let rows = sqlx::query_as::<_, ContestatieRow>(
+        "SELECT * FROM contestatii WHERE judet = ? ORDER BY medie_adm DESC",
+    )
+    .bind(county)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| from_row(row, year, &medii))
+        .collect())
+}
+
+pub async fn report(
+    data_dir: &std::path::Path,
+    year: i32,
+    county: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = db::create_pool(
+        db::dsn(&db::year_path(data_dir, year))?.as_str(),
+        false,
+        db::DEFAULT_POOL_SIZE,
+    )
+    .await?;
+
+    let counties = match county {
+        Some(county) => vec![county],
+        None => {
+            sqlx::query_scalar::<_, String>("SELECT code FROM counties ORDER BY code ASC")
+                .fetch_all(&db)
+                .await?
+        }
+    };
+
+    println!(
+        "{:<6} {:>10} {:>10} {:>14} {:>14}",
+        "Judet", "Modificari", "Urcari", "Medie +adm", "Locuri +"
+    );
+    for county in counties {
+        let changes = for_county(&db, year, &county).await?;
+        if changes.is_empty() {
+            continue;
+        }
+
+        let improved = changes.iter().filter(|x| x.improved()).count();
+        let (gain, places, n) = changes
+            .iter()
+            .filter_map(|x| {
+                Some((
+                    x.medie_admitere? - x.medie_admitere_initiala?,
+                    x.loc_initial? as f64 - x.loc? as f64,
+                ))
+            })
+            .fold((0.0, 0.0, 0), |(gain, places, n), (g, p)| {
+                (gain + g, places + p, n + 1)
+            });
+        let n = f64::max(n as f64, 1.0);
+
+        println!(
+            "{:<6} {:>10} {:>10} {:>14.3} {:>14.1}",
+            county,
+            changes.len(),
+            improved,
+            gain / n,
+            places / n
+        );
+    }
+
+    db.close().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(initial: f64, fin: f64, medie_en: f64, medie_adm: f64) -> ContestatieRow {
+        ContestatieRow {
+            id: "AB1".to_string(),
+            judet: "AB".to_string(),
+            nota_ro_initiala: Some(initial),
+            nota_ro: Some(fin),
+            nota_mate_initiala: Some(8.0),
+            nota_mate: Some(8.0),
+            nota_lm_initiala: None,
+            nota_lm: None,
+            medie_en: Some(medie_en),
+            medie_adm: Some(medie_adm),
+        }
+    }
+
+    #[test]
+    fn rank_is_one_based_and_shares_ties() {
+        let medii = [9.5, 9.0, 9.0, 8.0];
+
+        assert_eq!(rank(&medii, 9.9), 1);
+        assert_eq!(rank(&medii, 9.5), 1);
+        assert_eq!(rank(&medii, 9.0), 2);
+        assert_eq!(rank(&medii, 8.5), 4);
+        assert_eq!(rank(&medii, 7.0), 5);
+    }
+
+    #[test]
+    fn raise_without_overtaking_keeps_the_rank() {
+        // 8.00 -> 8.50 in Romanian moves the admission average from 7.60 to 7.80,
+        // still between the other two candidates
+        let medii = [9.0, 7.8, 7.0];
+        let appeal = from_row(row(8.0, 8.5, 8.25, 7.8), 2023, &medii);
+
+        assert_eq!(
+            appeal.medie_admitere_initiala.map(|x| (x * 100.0).round()),
+            Some(760.0)
+        );
+        assert_eq!(appeal.loc_initial, Some(2));
+        assert_eq!(appeal.loc, Some(2));
+        assert!(!appeal.improved());
+    }
+
+    #[test]
+    fn raise_past_another_candidate_improves_the_rank() {
+        let medii = [9.0, 7.8, 7.7, 7.0];
+        let appeal = from_row(row(8.0, 8.5, 8.25, 7.8), 2023, &medii);
+
+        assert_eq!(appeal.loc_initial, Some(3));
+        assert_eq!(appeal.loc, Some(2));
+        assert!(appeal.improved());
+    }
+
+    #[test]
+    fn initial_average_uses_the_weight_of_the_year() {
+        let before = from_row(row(8.0, 8.5, 8.25, 7.8), 2020, &[7.8]);
+        let after = from_row(row(8.0, 8.5, 8.25, 7.8), 2021, &[7.8]);
+
+        assert_eq!(before.medie_admitere_initiala, Some(7.8 - 0.75 * 0.25));
+        assert_eq!(after.medie_admitere_initiala, Some(7.8 - 0.8 * 0.25));
+    }
+
+    #[test]
+    fn lowered_grade_worsens_the_rank() {
+        let medii = [9.0, 7.5, 7.4];
+        let appeal = from_row(row(8.5, 8.0, 8.0, 7.4), 2023, &medii);
+
+        assert_eq!(appeal.loc_initial, Some(2));
+        assert_eq!(appeal.loc, Some(3));
+        assert!(!appeal.improved());
+    }
+
+    #[tokio::test]
+    async fn appeals_stay_in_their_county() {
+        let db = crate::db::memory_pool().await.unwrap();
+        sqlx::query(
+            "
+INSERT INTO counties (id, code, name) VALUES (1, 'AB', 'Alba'), (14, 'CV', 'Covasna');
+INSERT INTO evaluare (id, judet, scoala, nota_ro_initiala, nota_ro, nota_mate_initiala, nota_mate)
+VALUES ('X1', 'AB', 'S', 8.0, 8.5, 8.0, 8.0);
+INSERT INTO students
+    (id, round, provenienta, judet, medie_adm, medie_en, medie_abs, nota_ro, nota_mate, liceu, status, specializare_display)
+VALUES
+    ('X1', 1, 'S', 'AB', 8.2, 8.25, 8.0, 8.5, 8.0, 'L', 'unplaced', 'Nerepartizat'),
+    ('X1', 1, 'S', 'CV', 6.0, 6.0, 6.0, 6.0, 6.0, 'L', 'unplaced', 'Nerepartizat');",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let alba = for_county(&db, 2023, "AB").await.unwrap();
+        assert_eq!(alba.len(), 1);
+        assert_eq!(alba[0].medie_admitere, Some(8.2));
+        assert!(for_county(&db, 2023, "CV").await.unwrap().is_empty());
+    }
+}
@@ -1,33 +1,89 @@
+use crate::fetch::Fetcher;
+use crate::round::{self, Round};
 use scraper::{Html, Selector};
 use titlecase::titlecase;
 
-#[derive(Debug, Clone, sqlx::FromRow)]
-pub struct County {
-    #[sqlx(default)]
-    pub id: i32,
-    pub code: String,
-    pub name: String,
-}
+pub use repartizare_api::County;
 
-pub async fn get_all(year: i32) -> Result<Vec<County>, reqwest::Error> {
-    let mut result = Vec::new();
+/// Official county codes (SIRUTA), keyed by the two-letter code the ministry uses in its paths.
+/// `insert_county` stores these on every run; migration 05 only backfilled databases generated
+/// before ids were stored, from a copy of this table.
+const OFFICIAL_IDS: [(&str, i32); 42] = [
+    ("AB", 1),
+    ("AR", 2),
+    ("AG", 3),
+    ("BC", 4),
+    ("BH", 5),
+    ("BN", 6),
+    ("BT", 7),
+    ("BV", 8),
+    ("BR", 9),
+    ("BZ", 10),
+    ("CS", 11),
+    ("CJ", 12),
+    ("CT", 13),
+    ("CV", 14),
+    ("DB", 15),
+    ("DJ", 16),
+    ("GL", 17),
+    ("GJ", 18),
+    ("HR", 19),
+    ("HD", 20),
+    ("IL", 21),
+    ("IS", 22),
+    ("IF", 23),
+    ("MM", 24),
+    ("MH", 25),
+    ("MS", 26),
+    ("NT", 27),
+    ("OT", 28),
+    ("PH", 29),
+    ("SM", 30),
+    ("SJ", 31),
+    ("SB", 32),
+    ("SV", 33),
+    ("TR", 34),
+    ("TM", 35),
+    ("TL", 36),
+    ("VS", 37),
+    ("VL", 38),
+    ("VN", 39),
+    ("B", 40),
+    ("CL", 51),
+    ("GR", 52),
+];
 
-    let sel = Selector::parse(".county .card-body").unwrap();
-
-    let resp = reqwest::get(format!(
-        "http://static.admitere.edu.ro/{year}/repartizare/index.html"
-    ))
-    .await?;
+pub fn official_id(code: &str) -> Option<i32> {
+    OFFICIAL_IDS
+        .iter()
+        .find(|(official, _)| official.eq_ignore_ascii_case(code))
+        .map(|(_, id)| *id)
+}
 
-    if resp.status().as_u16() != 200 {
-        panic!("Year was probably deleted.")
-    }
+/// Whether the ministry has published results for `round` yet.
+pub async fn is_published(
+    fetcher: &Fetcher,
+    year: i32,
+    round: Round,
+) -> Result<bool, reqwest::Error> {
+    let page = fetcher
+        .get(&round::url(
+            fetcher.admitere_url(),
+            round,
+            year,
+            "index.html",
+        ))
+        .await?;
+    Ok(page.status.is_success())
+}
 
-    let body = resp.text().await?;
+/// Counties linked from a round's index page.
+fn parse(body: &str) -> Vec<County> {
+    let mut result = Vec::new();
 
-    let doc = Html::parse_document(&body);
+    let sel = Selector::parse(".county .card-body").unwrap();
+    let doc = Html::parse_document(body);
 
-    let mut i = 0;
     for element in doc.select(&sel) {
         match element.value().attr("href") {
             Some(val) => {
@@ -37,17 +93,130 @@ pub async fn get_all(year: i32) -> Result<Vec<County>, reqwest::Error> {
                     None => inner_val,
                 };
 
+                let code = String::from(val.trim_end_matches("/index.html"));
+                let id = official_id(&code);
+                if id.is_none() {
+                    tracing::warn!(
+                        code = code.as_str(),
+                        "county code missing from the official list"
+                    );
+                }
                 result.push(County {
-                    id: i,
-                    code: String::from(val.trim_end_matches("/index.html")),
-                    name: String::from(titlecase(inner_val.as_str())),
+                    id,
+                    code,
+                    name: titlecase(inner_val.as_str()),
                 });
-
-                i += 1;
             }
             None => continue,
         }
     }
 
-    Ok(result)
+    result
+}
+
+pub async fn get_all(
+    fetcher: &Fetcher,
+    year: i32,
+    round: Round,
+) -> Result<Vec<County>, Box<dyn std::error::Error>> {
+    let body = fetcher
+        .get_ok(&round::url(
+            fetcher.admitere_url(),
+            round,
+            year,
+            "index.html",
+        ))
+        .await
+        .map_err(|err| format!("Year was probably deleted: {err}"))?;
+
+    Ok(parse(&String::from_utf8_lossy(&body)))
+}
+
+/// Add a county, or update its id and name if its code is already there.
+pub(crate) async fn insert_county<'c, E>(county: &County, db: E) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Sqlite>,
+{
+    sqlx::query(
+        "
+INSERT INTO counties (id, code, name) VALUES (?, ?, ?)
+ON CONFLICT (code) DO UPDATE SET id = excluded.id, name = excluded.name",
+    )
+    .bind(county.id)
+    .bind(&county.code)
+    .bind(&county.name)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = include_str!("../tests/fixtures/admitere/2023/repartizare/index.html");
+
+    #[test]
+    fn parses_linked_counties() {
+        let counties = parse(INDEX);
+
+        assert_eq!(counties.len(), 2);
+        assert_eq!(counties[0].id, Some(1));
+        assert_eq!(counties[0].code, "AB");
+        assert_eq!(counties[1].id, Some(14));
+        assert_eq!(counties[1].code, "CV");
+    }
+
+    #[test]
+    fn names_are_titlecased() {
+        let counties = parse(INDEX);
+
+        assert_eq!(counties[0].name, "Alba");
+        assert_eq!(counties[1].name, "Covasna");
+    }
+
+    #[test]
+    fn unknown_code_has_no_id() {
+        let body =
+            r#"<div class="county"><a class="card-body" href="XX/index.html"> NOWHERE</a></div>"#;
+        let counties = parse(body);
+
+        assert_eq!(counties[0].code, "XX");
+        assert_eq!(counties[0].id, None);
+    }
+
+    #[tokio::test]
+    async fn migration_backfill_matches_official_ids() {
+        let db = crate::db::memory_pool().await.unwrap();
+        for (code, _) in OFFICIAL_IDS {
+            sqlx::query("INSERT INTO counties (code, name) VALUES (?, ?)")
+                .bind(code.to_lowercase())
+                .bind(code)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        // re-run the backfill from migration 05 over counties stored without an id
+        let migration = include_str!("../migrations/05_county_ids.sql");
+        let backfill = &migration[migration.find("UPDATE").unwrap()..];
+        let backfill = &backfill[..=backfill.find(';').unwrap()];
+        sqlx::query(backfill).execute(&db).await.unwrap();
+
+        let stored = sqlx::query_as::<_, (String, Option<i32>)>("SELECT code, id FROM counties")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), OFFICIAL_IDS.len());
+        for (code, id) in stored {
+            assert_eq!(id, official_id(&code), "{code}");
+        }
+    }
+
+    #[test]
+    fn official_id_ignores_case() {
+        assert_eq!(official_id("b"), Some(40));
+        assert_eq!(official_id("cl"), Some(51));
+        assert_eq!(official_id("XX"), None);
+    }
 }
@@ -3,11 +3,63 @@ use sqlx::{
     sqlite::{SqliteConnectOptions, SqlitePoolOptions},
     ConnectOptions,
 };
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-pub async fn create_pool(dsn: &str, create: bool) -> Result<sqlx::SqlitePool, sqlx::Error> {
+/// Path of the database holding `year`, inside the data directory `dir`.
+pub fn year_path(dir: &Path, year: i32) -> PathBuf {
+    dir.join(format!("{year}.db"))
+}
+
+/// Years that have a database in the data directory `dir`, in ascending order.
+pub fn discover_years(dir: &Path) -> std::io::Result<Vec<i32>> {
+    let mut years = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|x| x.to_str()) != Some("db") {
+            continue;
+        }
+        if let Some(year) = path.file_stem().and_then(|x| x.to_str()?.parse().ok()) {
+            years.push(year);
+        }
+    }
+    years.sort_unstable();
+    Ok(years)
+}
+
+pub fn dsn(path: &Path) -> Result<String, String> {
+    Ok(format!(
+        "sqlite://{}",
+        path.to_str().ok_or("Failed to create db path")?
+    ))
+}
+
+pub const DEFAULT_POOL_SIZE: u32 = 5;
+
+/// Write a consistent copy of the database at `from` to `to`, which must not exist yet. Safe
+/// while another process, like `generator watch`, is writing to `from`.
+pub async fn snapshot(from: &Path, to: &Path) -> Result<(), sqlx::Error> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(from)
+        .read_only(true)
+        .disable_statement_logging()
+        .clone()
+        .connect()
+        .await?;
+    sqlx::query("VACUUM INTO ?")
+        .bind(to.to_string_lossy())
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn create_pool(
+    dsn: &str,
+    create: bool,
+    max_connections: u32,
+) -> Result<sqlx::SqlitePool, sqlx::Error> {
     let db = SqlitePoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
         .connect_with(
             SqliteConnectOptions::from_str(dsn)?
                 .foreign_keys(true)
@@ -21,3 +73,24 @@ pub async fn create_pool(dsn: &str, create: bool) -> Result<sqlx::SqlitePool, sq
 
     Ok(db)
 }
+
+/// A fresh, migrated database that lives as long as the pool, for tests.
+pub async fn memory_pool() -> Result<sqlx::SqlitePool, sqlx::Error> {
+    // every connection to `:memory:` has a database of its own, so keep exactly one open
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(
+            SqliteConnectOptions::from_str("sqlite::memory:")?
+                .foreign_keys(true)
+                .disable_statement_logging()
+                .clone(),
+        )
+        .await?;
+
+    migrate!().run(&db).await?;
+
+    Ok(db)
+}
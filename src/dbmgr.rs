@@ -1,139 +1,437 @@
-use crate::{county::County, specializare::Specializare, student::Student};
+use crate::repository::{
+    self, CountyFilter, Error, Repository, SchoolFilter, SpecializationFilter, StudentFilter,
+};
+use crate::{
+    contestatie::{self, Contestatie},
+    county::County,
+    round::Round,
+    specializare::Specializare,
+    student::{Placement, Student},
+};
 use serde::Serialize;
-use std::sync::Arc;
+use std::any::Any;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 
+pub use repartizare_api::{FullSchool, FullSpec, GradeBucket, Participation, SpecShort, Unplaced};
+
+/// Identifies one version of a database file, so a regenerated file is noticed and reopened.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    modified: std::time::SystemTime,
+    len: u64,
+    /// Changes when the generator renames a new file over the old one.
+    inode: u64,
+}
+
+impl FileVersion {
+    async fn of(path: &std::path::Path) -> std::io::Result<FileVersion> {
+        // runs on every request, so keep the blocking stat off the executor
+        let meta = tokio::fs::metadata(path).await?;
+        Ok(FileVersion {
+            modified: meta.modified()?,
+            len: meta.len(),
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(&meta),
+            #[cfg(not(unix))]
+            inode: 0,
+        })
+    }
+}
+
+struct YearPool {
+    pool: sqlx::SqlitePool,
+    version: FileVersion,
+    /// Hash of the file contents, used as the ETag of every response built from it. Replicas
+    /// serving the same file agree on it, unlike the file's metadata.
+    etag: String,
+}
+
+fn hash_file(path: &std::path::Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:.32x}", hasher.finalize()))
+}
+
+/// A cached query result. The database's etag is part of the key, so a result computed from a
+/// file that has since been regenerated is never served.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    year: i32,
+    etag: String,
+    query: String,
+}
+
+type Cache = lru::LruCache<CacheKey, Arc<dyn Any + Send + Sync>>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Connection usage of one open year database.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    pub year: i32,
+    pub size: u32,
+    pub idle: usize,
+}
+
 pub struct DB {
     prefix: std::path::PathBuf,
-    pools: Arc<RwLock<std::collections::HashMap<i32, sqlx::SqlitePool>>>,
+    pool_size: u32,
+    pools: Arc<RwLock<std::collections::HashMap<i32, YearPool>>>,
+    cache: Option<Mutex<Cache>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl DB {
-    pub fn new(prefix: String) -> DB {
+    /// `cache_entries` bounds the in-memory cache of query results, 0 disables it.
+    pub fn new(prefix: std::path::PathBuf, pool_size: u32, cache_entries: usize) -> DB {
         DB {
-            prefix: std::path::Path::new(prefix.as_str()).to_owned(),
+            prefix,
+            pool_size,
             pools: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            cache: NonZeroUsize::new(cache_entries).map(|x| Mutex::new(lru::LruCache::new(x))),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         }
     }
 
-    pub async fn get_year_pool(
-        &self,
-        year: i32,
-    ) -> Result<sqlx::SqlitePool, Box<dyn std::error::Error>> {
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self
+                .cache
+                .as_ref()
+                .map_or(0, |cache| cache.lock().unwrap().len()),
+        }
+    }
+
+    /// Close every open year database, waiting for connections in use to be returned.
+    pub async fn close_all(&self) {
+        let pools: Vec<(i32, YearPool)> = self.pools.write().await.drain().collect();
+        for (year, pool) in pools {
+            pool.pool.close().await;
+            tracing::debug!(year, "closed database");
+        }
+    }
+
+    /// Years with a database in the data directory, whether or not they have been opened yet.
+    pub fn discover_years(&self) -> std::io::Result<Vec<i32>> {
+        crate::db::discover_years(&self.prefix)
+    }
+
+    /// Open `year`'s database and count the rows of its main tables.
+    pub async fn count_rows(&self, year: i32) -> Result<RowCounts, Box<dyn std::error::Error>> {
+        let pool = self.get_year_pool(year).await?;
+        let counts = sqlx::query_as::<_, RowCounts>(
+            "
+SELECT
+    (SELECT COUNT(*) FROM counties) AS counties,
+    (SELECT COUNT(*) FROM specializari) AS specializari,
+    (SELECT COUNT(*) FROM students) AS students",
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    pub fn pool_size(&self) -> u32 {
+        self.pool_size
+    }
+
+    pub async fn pool_stats(&self) -> Vec<PoolStats> {
+        let pools = self.pools.read().await;
+        let mut stats: Vec<PoolStats> = pools
+            .iter()
+            .map(|(year, pool)| PoolStats {
+                year: *year,
+                size: pool.pool.size(),
+                idle: pool.pool.num_idle(),
+            })
+            .collect();
+        stats.sort_by_key(|x| x.year);
+        stats
+    }
+
+    /// Return the cached result of `query` on `year`'s database, running `load` on a miss.
+    async fn cached<T, F, Fut>(&self, year: i32, query: String, load: F) -> Result<Arc<T>, Error>
+    where
+        T: Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let etag = self.etag(year).await?;
+        let key = CacheKey { year, etag, query };
+
+        if let Some(cache) = &self.cache {
+            let hit = cache.lock().unwrap().get(&key).cloned();
+            if let Some(Ok(hit)) = hit.map(|x| x.downcast::<T>()) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(hit);
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let value = Arc::new(load().await?);
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().put(key, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Drop everything cached for `year`, once its database has been reopened.
+    fn evict_year(&self, year: i32) {
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap();
+            let stale: Vec<CacheKey> = cache
+                .iter()
+                .filter(|(key, _)| key.year == year)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in stale {
+                cache.pop(&key);
+            }
+        }
+    }
+
+    pub async fn get_year_pool(&self, year: i32) -> Result<sqlx::SqlitePool, Error> {
+        Ok(self.open_year(year).await?.0)
+    }
+
+    /// Tag identifying the current contents of a year's database, changing whenever the
+    /// generator rewrites it.
+    pub async fn etag(&self, year: i32) -> Result<String, Error> {
+        Ok(self.open_year(year).await?.1)
+    }
+
+    async fn open_year(&self, year: i32) -> Result<(sqlx::SqlitePool, String), Error> {
+        let path = crate::db::year_path(&self.prefix, year);
+        // the generator replaces or updates the file underneath us, reopen it when that happens
+        let version = FileVersion::of(&path)
+            .await
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Error::UnknownYear(year),
+                _ => Error::Io(err),
+            })?;
+
         let pools = self.pools.read().await;
 
         if let Some(pool) = pools.get(&year) {
-            return Ok(pool.clone());
+            if pool.version == version {
+                return Ok((pool.pool.clone(), pool.etag.clone()));
+            }
         }
 
         drop(pools);
 
         let mut pools = self.pools.write().await;
 
-        let pool = crate::db::create_pool(
-            format!(
-                "sqlite://{}",
-                self.prefix
-                    .join(format!("{year}.db"))
-                    .to_str()
-                    .ok_or("Failed to create db path")?
-            )
-            .as_str(),
-            false,
-        )
-        .await?;
-        if let Some(old_pool) = pools.insert(year, pool) {
-            old_pool.close().await
+        // another request may have reopened it while we waited for the lock
+        if let Some(pool) = pools.get(&year) {
+            if pool.version == version {
+                return Ok((pool.pool.clone(), pool.etag.clone()));
+            }
         }
 
-        Ok(pools.get(&year).unwrap().clone())
-    }
+        let dsn = crate::db::dsn(&path)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        let pool = crate::db::create_pool(&dsn, false, self.pool_size).await?;
+        // opening runs pending migrations, which may have just changed the file
+        let version = FileVersion::of(&path).await?;
+        // hashed once per version of the file, not per request
+        let etag = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || hash_file(&path))
+                .await
+                .map_err(std::io::Error::other)??
+        };
+        let opened = (pool.clone(), etag.clone());
+        if let Some(old_pool) = pools.insert(
+            year,
+            YearPool {
+                pool,
+                version,
+                etag,
+            },
+        ) {
+            self.evict_year(year);
+            old_pool.pool.close().await
+        }
 
-    pub async fn get_counties(&self, year: i32) -> Result<Vec<County>, Box<dyn std::error::Error>> {
-        let pool = self.get_year_pool(year).await?;
-        let counties = sqlx::query_as::<_, County>("SELECT * FROM counties ORDER BY code ASC;")
-            .fetch_all(&pool)
-            .await?;
+        Ok(opened)
+    }
 
-        Ok(counties)
+    pub async fn get_counties(&self, year: i32) -> Result<Arc<Vec<County>>, Error> {
+        self.cached(year, "counties".to_string(), || async move {
+            self.counties(year, &CountyFilter::new()).await
+        })
+        .await
     }
 
     pub async fn get_schools(
         &self,
         year: i32,
+        round: Round,
         county: &str,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let pool = self.get_year_pool(year).await?;
+    ) -> Result<Arc<Vec<String>>, Error> {
+        let query = format!("schools/{round}/{county}");
+        self.cached(year, query, || async move {
+            let filter = SchoolFilter::new().round(round).county(county);
+            let schools = self.schools(year, &filter).await?;
+            Ok(schools.into_iter().map(|x| x.liceu).collect())
+        })
+        .await
+    }
 
-        #[derive(sqlx::FromRow)]
-        struct Result {
-            liceu: String,
-        }
+    pub async fn get_full_school(
+        &self,
+        year: i32,
+        round: Round,
+        county: &str,
+        school: &str,
+    ) -> Result<Arc<FullSchool>, Error> {
+        let query = format!("fullSchool/{round}/{county}/{school}");
+        self.cached(year, query, || async move {
+            let filter = SpecializationFilter::new()
+                .round(round)
+                .county(county)
+                .school(school);
+            let specs = self.specializations(year, &filter).await?;
 
-        let schools = sqlx::query_as::<_, Result>(
-            "SELECT liceu FROM specializari WHERE judet = ? GROUP BY liceu ORDER BY liceu ASC",
-        )
-        .bind(county)
-        .fetch_all(&pool)
-        .await?;
+            let mut school = FullSchool {
+                specializari_short: specs
+                    .iter()
+                    .map(|spec| SpecShort {
+                        id: spec.id,
+                        name: spec.name.clone(),
+                    })
+                    .collect(),
+                specializari: std::collections::HashMap::new(),
+            };
 
-        Ok(schools.iter().map(|x| x.liceu.clone()).collect())
+            for spec in specs {
+                let filter = StudentFilter::new()
+                    .round(round)
+                    .county(county)
+                    .specialization(spec.id);
+                let elevi = self.students(year, &filter).await?;
+                school
+                    .specializari
+                    .insert(spec.id, FullSpec { elevi, spec });
+            }
+
+            Ok(school)
+        })
+        .await
     }
 
-    pub async fn get_full_school(
+    pub async fn get_unplaced(
         &self,
         year: i32,
+        round: Round,
         county: &str,
-        school: &str,
-    ) -> Result<FullSchool, Box<dyn std::error::Error>> {
-        let pool = self.get_year_pool(year).await?;
+    ) -> Result<Unplaced, Error> {
+        let filter = StudentFilter::new()
+            .round(round)
+            .county(county)
+            .status(Placement::Unplaced);
+        let elevi = self.students(year, &filter).await?;
 
-        let specs = sqlx::query_as::<_, SpecShort>(
-            "SELECT id, name FROM specializari WHERE judet = ? AND liceu = ? ORDER BY id ASC",
+        let distribution = sqlx::query_as::<_, GradeBucket>(
+            "
+SELECT CAST(medie_adm AS INTEGER) AS grade, COUNT(*) AS count
+FROM students
+WHERE judet = ? AND round = ? AND status = ? AND medie_adm IS NOT NULL
+GROUP BY grade ORDER BY grade ASC",
         )
         .bind(county)
-        .bind(school)
-        .fetch_all(&pool)
+        .bind(round)
+        .bind(Placement::Unplaced)
+        .fetch_all(&self.get_year_pool(year).await?)
         .await?;
 
-        let mut school = FullSchool {
-            specializari: std::collections::HashMap::new(),
-            specializari_short: specs,
-        };
+        Ok(Unplaced {
+            elevi,
+            distribution,
+        })
+    }
 
-        for spec in &school.specializari_short {
-            school.specializari.insert(spec.id, FullSpec {
-                elevi: sqlx::query_as::<_, Student>(
-                    "SELECT * FROM students WHERE judet = ? AND id_specializare = ? ORDER BY medie_adm DESC",
-                ).bind(county).bind(spec.id).fetch_all(&pool).await?,
+    pub async fn get_appeals(&self, year: i32, county: &str) -> Result<Vec<Contestatie>, Error> {
+        let pool = self.get_year_pool(year).await?;
+        Ok(contestatie::for_county(&pool, year, county).await?)
+    }
 
-                spec: sqlx::query_as::<_, Specializare>(
-                    "SELECT * FROM specializari WHERE judet = ? AND id = ?",
-                ).bind(county).bind(spec.id).fetch_one(&pool).await?,
-            });
-        }
+    pub async fn get_participation(&self, year: i32, county: &str) -> Result<Participation, Error> {
+        let pool = self.get_year_pool(year).await?;
+
+        let participation = sqlx::query_as::<_, Participation>(
+            "
+SELECT
+    COUNT(*) AS inscrisi,
+    COUNT(medie) AS prezenti,
+    COUNT(*) - COUNT(medie) AS absenti,
+    COUNT(CASE WHEN nota_ro_contestatie IS NOT NULL
+        OR nota_mate_contestatie IS NOT NULL
+        OR nota_lm_contestatie IS NOT NULL THEN 1 END) AS contestatii,
+    COUNT(CASE WHEN EXISTS (
+        SELECT 1 FROM students s WHERE s.judet = e.judet AND s.round = 1 AND s.id = e.id
+    ) THEN 1 END) AS admitere
+FROM evaluare e
+WHERE judet = ?",
+        )
+        .bind(county)
+        .fetch_one(&pool)
+        .await?;
 
-        Ok(school)
+        Ok(participation)
     }
 }
 
-#[derive(Serialize, sqlx::FromRow)]
-pub struct SpecShort {
-    pub id: i32,
-    pub name: String,
-}
+impl Repository for DB {
+    async fn years(&self) -> Result<Vec<i32>, Error> {
+        Ok(self.discover_years()?)
+    }
+
+    async fn counties(&self, year: i32, filter: &CountyFilter) -> Result<Vec<County>, Error> {
+        repository::sql::counties(&self.get_year_pool(year).await?, filter).await
+    }
+
+    async fn schools(
+        &self,
+        year: i32,
+        filter: &SchoolFilter,
+    ) -> Result<Vec<repository::School>, Error> {
+        repository::sql::schools(&self.get_year_pool(year).await?, filter).await
+    }
+
+    async fn specializations(
+        &self,
+        year: i32,
+        filter: &SpecializationFilter,
+    ) -> Result<Vec<Specializare>, Error> {
+        repository::sql::specializations(&self.get_year_pool(year).await?, filter).await
+    }
 
-#[derive(Serialize)]
-pub struct FullSpec {
-    pub elevi: Vec<Student>,
-    #[serde(rename = "sp")]
-    pub spec: Specializare,
+    async fn students(&self, year: i32, filter: &StudentFilter) -> Result<Vec<Student>, Error> {
+        repository::sql::students(&self.get_year_pool(year).await?, filter).await
+    }
 }
 
-#[derive(Serialize)]
-pub struct FullSchool {
-    #[serde(rename = "specs")]
-    pub specializari_short: Vec<SpecShort>,
-    #[serde(rename = "spec_data")]
-    pub specializari: std::collections::HashMap<i32, FullSpec>,
+#[derive(Debug, Serialize, sqlx::FromRow, utoipa::ToSchema)]
+pub struct RowCounts {
+    pub counties: i64,
+    pub specializari: i64,
+    pub students: i64,
 }
@@ -0,0 +1,173 @@
+use crate::county::County;
+use crate::fetch::Fetcher;
+use crate::source;
+use sqlx::Executor;
+
+#[derive(Debug, serde::Deserialize)]
+struct RawEvaluare {
+    #[serde(alias = "n")]
+    id: String,
+    #[serde(alias = "s")]
+    scoala: String,
+    #[serde(alias = "sc")]
+    _id_scoala: String,
+
+    #[serde(alias = "ri")]
+    nota_ro_initiala: String,
+    #[serde(alias = "rc", default)]
+    nota_ro_contestatie: String,
+    #[serde(alias = "rf")]
+    nota_ro: String,
+
+    #[serde(alias = "mi")]
+    nota_mate_initiala: String,
+    #[serde(alias = "mc", default)]
+    nota_mate_contestatie: String,
+    #[serde(alias = "mf")]
+    nota_mate: String,
+
+    #[serde(alias = "lm", default)]
+    limba_materna: String,
+    #[serde(alias = "lmi", default)]
+    nota_lm_initiala: String,
+    #[serde(alias = "lmc", default)]
+    nota_lm_contestatie: String,
+    #[serde(alias = "lmf", default)]
+    nota_lm: String,
+
+    #[serde(alias = "mev")]
+    medie: String,
+}
+
+/// A candidate's Evaluare Națională results, before and after appeals.
+#[derive(Debug, serde::Serialize, sqlx::FromRow)]
+pub struct Evaluare {
+    pub id: String,
+    pub judet: String,
+    pub scoala: String,
+
+    pub nota_ro_initiala: Option<f64>,
+    pub nota_ro_contestatie: Option<f64>,
+    pub nota_ro: Option<f64>,
+
+    pub nota_mate_initiala: Option<f64>,
+    pub nota_mate_contestatie: Option<f64>,
+    pub nota_mate: Option<f64>,
+
+    pub limba_materna: Option<String>,
+    pub nota_lm_initiala: Option<f64>,
+    pub nota_lm_contestatie: Option<f64>,
+    pub nota_lm: Option<f64>,
+
+    pub medie: Option<f64>,
+}
+
+impl Evaluare {
+    fn from_raw(st: &RawEvaluare, county: &County) -> Evaluare {
+        // absent candidates and missing appeals show up as text instead of a grade
+        let grade = |val: &str| val.trim().parse().ok();
+        Evaluare {
+            id: st.id.clone(),
+            judet: county.code.clone(),
+            scoala: st.scoala.clone(),
+
+            nota_ro_initiala: grade(&st.nota_ro_initiala),
+            nota_ro_contestatie: grade(&st.nota_ro_contestatie),
+            nota_ro: grade(&st.nota_ro),
+
+            nota_mate_initiala: grade(&st.nota_mate_initiala),
+            nota_mate_contestatie: grade(&st.nota_mate_contestatie),
+            nota_mate: grade(&st.nota_mate),
+
+            limba_materna: match st.limba_materna.trim() {
+                "" | "-" => None,
+                lm => Some(lm.to_string()),
+            },
+            nota_lm_initiala: grade(&st.nota_lm_initiala),
+            nota_lm_contestatie: grade(&st.nota_lm_contestatie),
+            nota_lm: grade(&st.nota_lm),
+
+            medie: grade(&st.medie),
+        }
+    }
+}
+
+fn url(fetcher: &Fetcher, year: i32, file: &str) -> String {
+    format!("{}/{year}/rezultate/{file}", fetcher.evaluare_url())
+}
+
+/// Whether the Evaluare Națională results for `year` are still online.
+pub async fn is_published(fetcher: &Fetcher, year: i32) -> Result<bool, reqwest::Error> {
+    let page = fetcher.get(&url(fetcher, year, "index.html")).await?;
+    Ok(page.status.is_success())
+}
+
+fn parse(body: &[u8], county: &County) -> Result<Vec<Evaluare>, serde_json::Error> {
+    Ok(serde_json::from_slice::<Vec<RawEvaluare>>(body)?
+        .iter()
+        .map(|x| Evaluare::from_raw(x, county))
+        .collect::<Vec<Evaluare>>())
+}
+
+/// Download the county's Evaluare Națională results.
+/// Returns `None` if the published file is unchanged since the last run.
+pub async fn fetch_evaluare(
+    fetcher: &Fetcher,
+    year: i32,
+    county: &County,
+    db: &sqlx::Pool<sqlx::Sqlite>,
+    force: bool,
+) -> Result<Option<source::Fetched>, Box<dyn std::error::Error>> {
+    let candidates = url(
+        fetcher,
+        year,
+        &format!("{}/data/candidate.json", county.code),
+    );
+    source::fetch(fetcher, db, &candidates, force).await
+}
+
+/// Replace the county's Evaluare Națională results with the downloaded file, inside the caller's
+/// transaction.
+pub async fn replace_evaluare(
+    fetched: &source::Fetched,
+    county: &County,
+    db: &mut sqlx::SqliteConnection,
+) -> Result<source::Refresh, Box<dyn std::error::Error>> {
+    let results = parse(&fetched.body, county)?;
+    let refresh = source::Refresh {
+        rows: results.len(),
+        bytes: fetched.body.len(),
+    };
+
+    sqlx::query("DELETE FROM evaluare WHERE judet = ?")
+        .bind(&county.code)
+        .execute(&mut *db)
+        .await?;
+    for ev in results {
+        db.execute(sqlx::query("
+INSERT INTO evaluare
+    (id, judet, scoala, nota_ro_initiala, nota_ro_contestatie, nota_ro, nota_mate_initiala, nota_mate_contestatie, nota_mate, limba_materna, nota_lm_initiala, nota_lm_contestatie, nota_lm, medie)
+VALUES
+    (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+)
+        .bind(&ev.id)
+        .bind(&ev.judet)
+        .bind(&ev.scoala)
+        .bind(ev.nota_ro_initiala)
+        .bind(ev.nota_ro_contestatie)
+        .bind(ev.nota_ro)
+        .bind(ev.nota_mate_initiala)
+        .bind(ev.nota_mate_contestatie)
+        .bind(ev.nota_mate)
+        .bind(&ev.limba_materna)
+        .bind(ev.nota_lm_initiala)
+        .bind(ev.nota_lm_contestatie)
+        .bind(ev.nota_lm)
+        .bind(ev.medie)
+    )
+        .await?;
+    }
+    fetched.record(db).await?;
+
+    Ok(refresh)
+}
@@ -0,0 +1,184 @@
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub const ADMITERE_URL: &str = "http://static.admitere.edu.ro";
+pub const EVALUARE_URL: &str = "http://static.evaluare.edu.ro";
+
+// How hard the scraper is allowed to hit the ministry servers. Not a doc comment, since
+// clap would use it as the about text of every subcommand that flattens these options.
+#[derive(Debug, Clone, clap::Args)]
+pub struct FetchOptions {
+    /// Seconds before a single request is abandoned
+    #[clap(long, default_value_t = 30)]
+    pub timeout: u64,
+    /// Maximum number of requests in flight at once
+    #[clap(long, default_value_t = 4)]
+    pub concurrency: usize,
+    /// How many times a failed request is retried
+    #[clap(long, default_value_t = 5)]
+    pub retries: u32,
+    /// Milliseconds to wait before the first retry, doubled on every attempt
+    #[clap(long, default_value_t = 500)]
+    pub backoff: u64,
+    /// Maximum number of requests started per second
+    #[clap(long, default_value_t = 5.0)]
+    pub rate: f64,
+    /// Where the repartizare results are published, e.g. a local mirror
+    #[clap(long, default_value = ADMITERE_URL)]
+    pub admitere_url: String,
+    /// Where the Evaluare Națională results are published
+    #[clap(long, default_value = EVALUARE_URL)]
+    pub evaluare_url: String,
+}
+
+/// A fully downloaded response.
+pub struct Page {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Shared HTTP client for the scraper, with a concurrency limit, a rate limit and retries.
+pub struct Fetcher {
+    client: reqwest::Client,
+    permits: Semaphore,
+    retries: u32,
+    backoff: Duration,
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+    downloaded: AtomicU64,
+    admitere_url: String,
+    evaluare_url: String,
+}
+
+impl Fetcher {
+    pub fn new(opts: &FetchOptions) -> Result<Fetcher, reqwest::Error> {
+        Ok(Fetcher {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(opts.timeout))
+                .connect_timeout(Duration::from_secs(opts.timeout.min(10)))
+                .user_agent(concat!(
+                    env!("CARGO_PKG_NAME"),
+                    "/",
+                    env!("CARGO_PKG_VERSION")
+                ))
+                .build()?,
+            permits: Semaphore::new(opts.concurrency.max(1)),
+            retries: opts.retries,
+            backoff: Duration::from_millis(opts.backoff),
+            interval: if opts.rate > 0.0 {
+                Duration::from_secs_f64(1.0 / opts.rate)
+            } else {
+                Duration::ZERO
+            },
+            next_slot: Mutex::new(Instant::now()),
+            downloaded: AtomicU64::new(0),
+            admitere_url: opts.admitere_url.trim_end_matches('/').to_string(),
+            evaluare_url: opts.evaluare_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn admitere_url(&self) -> &str {
+        &self.admitere_url
+    }
+
+    pub fn evaluare_url(&self) -> &str {
+        &self.evaluare_url
+    }
+
+    /// Wait until the rate limit allows another request to start.
+    async fn throttle(&self) {
+        let mut next_slot = self.next_slot.lock().await;
+        let now = Instant::now();
+        let start = (*next_slot).max(now);
+        *next_slot = start + self.interval;
+        drop(next_slot);
+
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// Exponential backoff with full jitter.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let max = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF);
+        max.mul_f64(fastrand::f64())
+    }
+
+    /// Total response bytes received so far.
+    pub fn downloaded(&self) -> u64 {
+        self.downloaded.load(Ordering::Relaxed)
+    }
+
+    async fn try_get(&self, url: &str, headers: &HeaderMap) -> Result<Page, reqwest::Error> {
+        let started = Instant::now();
+        let resp = self.client.get(url).headers(headers.clone()).send().await?;
+
+        let page = Page {
+            status: resp.status(),
+            headers: resp.headers().clone(),
+            body: resp.bytes().await?.to_vec(),
+        };
+        self.downloaded
+            .fetch_add(page.body.len() as u64, Ordering::Relaxed);
+        tracing::debug!(
+            url,
+            status = page.status.as_u16(),
+            bytes = page.body.len(),
+            duration_ms = started.elapsed().as_millis() as u64,
+            "fetched"
+        );
+
+        Ok(page)
+    }
+
+    /// GET `url`, retrying connection errors, timeouts, throttling and server errors.
+    /// Other error statuses are returned as a `Page` for the caller to handle.
+    pub async fn get_with(&self, url: &str, headers: HeaderMap) -> Result<Page, reqwest::Error> {
+        let _permit = self.permits.acquire().await.unwrap();
+
+        let mut attempt = 0;
+        loop {
+            self.throttle().await;
+
+            let result = self.try_get(url, &headers).await;
+            let retry = match &result {
+                Ok(page) => {
+                    page.status.is_server_error() || page.status == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(err) => {
+                    err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+                }
+            };
+
+            if !retry || attempt >= self.retries {
+                return result;
+            }
+
+            let wait = self.backoff(attempt);
+            tracing::warn!(url, attempt = attempt + 1, wait = ?wait, "retrying");
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Page, reqwest::Error> {
+        self.get_with(url, HeaderMap::new()).await
+    }
+
+    /// GET `url` and fail on any non-success status.
+    pub async fn get_ok(&self, url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let page = self.get(url).await?;
+        if !page.status.is_success() {
+            return Err(format!("{url} returned {}", page.status).into());
+        }
+        Ok(page.body)
+    }
+}
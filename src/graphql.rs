@@ -0,0 +1,483 @@
+use crate::config::ServerConfig;
+use crate::dbmgr::{FullSchool, DB};
+use crate::round::Round;
+use crate::specializare::Specializare;
+use crate::student::{Placement, Student};
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Enum, Object, Result};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::response::{Html, IntoResponse};
+use axum::Extension;
+use std::sync::Arc;
+
+pub type AdmSchema = async_graphql::Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Deepest query accepted, enough for years → counties → schools → specializations → students.
+const MAX_DEPTH: usize = 10;
+/// Lists count once per row they are expected to hold, so walking every county, school and
+/// specialization of a year is rejected while one county's schools are not.
+const MAX_COMPLEXITY: usize = 5000;
+
+/// Expected list lengths, for the complexity of a query.
+const YEARS: usize = 10;
+const COUNTIES: usize = 42;
+const SCHOOLS: usize = 50;
+const SPECIALIZATIONS: usize = 10;
+const STUDENTS: usize = 30;
+const UNPLACED: usize = 200;
+
+fn db<'a>(ctx: &Context<'a>) -> Result<&'a Arc<DB>> {
+    ctx.data::<Arc<DB>>()
+}
+
+fn round(round: i32) -> Result<Round> {
+    Ok(Round::try_from(round)?)
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "crate::student::Placement")]
+enum PlacementKind {
+    Placed,
+    Unplaced,
+    Redistributed,
+    SpecialSession,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Years with published results.
+    #[graphql(complexity = "YEARS * child_complexity")]
+    async fn years(&self, ctx: &Context<'_>) -> Result<Vec<Year>> {
+        let config = ctx.data::<Arc<ServerConfig>>()?;
+        Ok(config
+            .years
+            .iter()
+            .map(|year| Year { year: *year })
+            .collect())
+    }
+
+    async fn year(&self, ctx: &Context<'_>, year: i32) -> Result<Option<Year>> {
+        let config = ctx.data::<Arc<ServerConfig>>()?;
+        Ok(config.years.contains(&year).then_some(Year { year }))
+    }
+}
+
+pub struct Year {
+    year: i32,
+}
+
+#[Object]
+impl Year {
+    async fn year(&self) -> i32 {
+        self.year
+    }
+
+    /// Counties of the year, optionally only those with the given codes.
+    #[graphql(complexity = "codes.as_ref().map_or(COUNTIES, Vec::len) * child_complexity")]
+    async fn counties(&self, ctx: &Context<'_>, codes: Option<Vec<String>>) -> Result<Vec<County>> {
+        let counties = db(ctx)?.get_counties(self.year).await?;
+        Ok(counties
+            .iter()
+            .filter(|county| {
+                codes
+                    .as_ref()
+                    .is_none_or(|codes| codes.iter().any(|x| x.eq_ignore_ascii_case(&county.code)))
+            })
+            .map(|county| County {
+                year: self.year,
+                county: county.clone(),
+            })
+            .collect())
+    }
+
+    async fn county(&self, ctx: &Context<'_>, code: String) -> Result<Option<County>> {
+        let counties = db(ctx)?.get_counties(self.year).await?;
+        Ok(counties
+            .iter()
+            .find(|county| county.code.eq_ignore_ascii_case(&code))
+            .map(|county| County {
+                year: self.year,
+                county: county.clone(),
+            }))
+    }
+}
+
+pub struct County {
+    year: i32,
+    county: crate::county::County,
+}
+
+#[Object]
+impl County {
+    /// SIRUTA code, null for a code missing from the official list.
+    async fn id(&self) -> Option<i32> {
+        self.county.id
+    }
+
+    async fn code(&self) -> &str {
+        &self.county.code
+    }
+
+    async fn name(&self) -> &str {
+        &self.county.name
+    }
+
+    /// High schools with seats in `round`, optionally only those whose name contains `search`.
+    #[graphql(complexity = "SCHOOLS * child_complexity")]
+    async fn schools(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] round: i32,
+        search: Option<String>,
+    ) -> Result<Vec<School>> {
+        let round = self::round(round)?;
+        let search = search.map(|x| x.to_lowercase());
+        let schools = db(ctx)?
+            .get_schools(self.year, round, &self.county.code)
+            .await?;
+        Ok(schools
+            .iter()
+            .filter(|name| {
+                search
+                    .as_ref()
+                    .is_none_or(|search| name.to_lowercase().contains(search))
+            })
+            .map(|name| School {
+                year: self.year,
+                round,
+                county: self.county.code.clone(),
+                name: name.clone(),
+            })
+            .collect())
+    }
+
+    async fn school(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default = 1)] round: i32,
+    ) -> Result<Option<School>> {
+        let round = self::round(round)?;
+        let schools = db(ctx)?
+            .get_schools(self.year, round, &self.county.code)
+            .await?;
+        Ok(schools.contains(&name).then(|| School {
+            year: self.year,
+            round,
+            county: self.county.code.clone(),
+            name,
+        }))
+    }
+
+    /// Candidates left without a seat in `round`, best admission average first.
+    #[graphql(complexity = "limit.unwrap_or(UNPLACED).min(UNPLACED) * child_complexity")]
+    async fn unplaced(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] round: i32,
+        min_average: Option<f64>,
+        max_average: Option<f64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<StudentNode>> {
+        let round = self::round(round)?;
+        let unplaced = db(ctx)?
+            .get_unplaced(self.year, round, &self.county.code)
+            .await?;
+        let filter = StudentFilter {
+            status: None,
+            min_average,
+            max_average,
+            limit,
+        };
+        Ok(filter.apply(self.year, &unplaced.elevi))
+    }
+
+    async fn participation(&self, ctx: &Context<'_>) -> Result<ParticipationNode> {
+        let participation = db(ctx)?
+            .get_participation(self.year, &self.county.code)
+            .await?;
+        Ok(ParticipationNode(participation))
+    }
+}
+
+struct StudentFilter {
+    status: Option<PlacementKind>,
+    min_average: Option<f64>,
+    max_average: Option<f64>,
+    limit: Option<usize>,
+}
+
+impl StudentFilter {
+    fn matches(&self, student: &Student) -> bool {
+        let average = student.medie_admitere;
+        self.status
+            .is_none_or(|status| Placement::from(status) == student.status)
+            && self
+                .min_average
+                .is_none_or(|min| matches!(average, Some(x) if x >= min))
+            && self
+                .max_average
+                .is_none_or(|max| matches!(average, Some(x) if x <= max))
+    }
+
+    fn apply(&self, year: i32, students: &[Student]) -> Vec<StudentNode> {
+        students
+            .iter()
+            .filter(|student| self.matches(student))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|student| StudentNode {
+                year,
+                student: student.clone(),
+            })
+            .collect()
+    }
+}
+
+pub struct School {
+    year: i32,
+    round: Round,
+    county: String,
+    name: String,
+}
+
+impl School {
+    async fn load(&self, ctx: &Context<'_>) -> Result<Arc<FullSchool>> {
+        Ok(db(ctx)?
+            .get_full_school(self.year, self.round, &self.county, &self.name)
+            .await?)
+    }
+}
+
+#[Object]
+impl School {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn county(&self) -> &str {
+        &self.county
+    }
+
+    async fn round(&self) -> i32 {
+        self.round as i32
+    }
+
+    #[graphql(complexity = "SPECIALIZATIONS * child_complexity")]
+    async fn specializations(
+        &self,
+        ctx: &Context<'_>,
+        bilingual: Option<bool>,
+        profile: Option<String>,
+    ) -> Result<Vec<Specialization>> {
+        let school = self.load(ctx).await?;
+        Ok(school
+            .specializari_short
+            .iter()
+            .filter_map(|short| school.specializari.get(&short.id))
+            .filter(|full| bilingual.is_none_or(|x| full.spec.bilingv == x))
+            .filter(|full| {
+                profile
+                    .as_ref()
+                    .is_none_or(|x| full.spec.profil.eq_ignore_ascii_case(x))
+            })
+            .map(|full| Specialization {
+                year: self.year,
+                spec: full.spec.clone(),
+                students: full.elevi.clone(),
+            })
+            .collect())
+    }
+}
+
+pub struct Specialization {
+    year: i32,
+    spec: Specializare,
+    students: Vec<Student>,
+}
+
+#[Object]
+impl Specialization {
+    async fn id(&self) -> i32 {
+        self.spec.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    async fn specialization(&self) -> &str {
+        &self.spec.specializare
+    }
+
+    async fn profile(&self) -> &str {
+        &self.spec.profil
+    }
+
+    async fn track(&self) -> &str {
+        &self.spec.filiera
+    }
+
+    async fn environment(&self) -> &str {
+        &self.spec.mediu
+    }
+
+    async fn bilingual(&self) -> bool {
+        self.spec.bilingv
+    }
+
+    async fn seats(&self) -> Option<i32> {
+        self.spec.locuri
+    }
+
+    async fn filled(&self) -> Option<i32> {
+        self.spec.ocupate
+    }
+
+    async fn last_admission_average(&self) -> Option<f64> {
+        self.spec.ultima_medie
+    }
+
+    async fn previous_last_admission_average(&self) -> Option<f64> {
+        self.spec.ultima_medie_anterior
+    }
+
+    /// Candidates placed here, best admission average first.
+    #[graphql(complexity = "limit.unwrap_or(STUDENTS).min(STUDENTS) * child_complexity")]
+    async fn students(
+        &self,
+        status: Option<PlacementKind>,
+        min_average: Option<f64>,
+        max_average: Option<f64>,
+        limit: Option<usize>,
+    ) -> Vec<StudentNode> {
+        let filter = StudentFilter {
+            status,
+            min_average,
+            max_average,
+            limit,
+        };
+        filter.apply(self.year, &self.students)
+    }
+}
+
+/// A candidate, named `Student` in the schema.
+pub struct StudentNode {
+    year: i32,
+    student: Student,
+}
+
+#[Object(name = "Student")]
+impl StudentNode {
+    async fn id(&self) -> &str {
+        &self.student.id
+    }
+
+    async fn county(&self) -> &str {
+        &self.student.judet
+    }
+
+    async fn round(&self) -> i32 {
+        self.student.round as i32
+    }
+
+    async fn status(&self) -> PlacementKind {
+        self.student.status.into()
+    }
+
+    async fn origin_school(&self) -> &str {
+        &self.student.provenienta
+    }
+
+    async fn admission_average(&self) -> Option<f64> {
+        self.student.medie_admitere
+    }
+
+    async fn national_exam_average(&self) -> Option<f64> {
+        self.student.medie_evaluare
+    }
+
+    async fn graduation_average(&self) -> Option<f64> {
+        self.student.medie_absolvire
+    }
+
+    async fn romanian_grade(&self) -> Option<f64> {
+        self.student.nota_romana
+    }
+
+    async fn math_grade(&self) -> Option<f64> {
+        self.student.nota_mate
+    }
+
+    async fn school_name(&self) -> &str {
+        &self.student.liceu
+    }
+
+    /// The specialization the candidate was placed in, `null` for unplaced candidates.
+    async fn specialization(&self, ctx: &Context<'_>) -> Result<Option<Specialization>> {
+        let id = match self.student.id_specializare {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let school = School {
+            year: self.year,
+            round: self.student.round,
+            county: self.student.judet.clone(),
+            name: self.student.liceu.clone(),
+        };
+        let school = school.load(ctx).await?;
+        Ok(school.specializari.get(&id).map(|full| Specialization {
+            year: self.year,
+            spec: full.spec.clone(),
+            students: full.elevi.clone(),
+        }))
+    }
+}
+
+pub struct ParticipationNode(crate::dbmgr::Participation);
+
+#[Object(name = "Participation")]
+impl ParticipationNode {
+    async fn registered(&self) -> i64 {
+        self.0.inscrisi
+    }
+
+    async fn present(&self) -> i64 {
+        self.0.prezenti
+    }
+
+    async fn absent(&self) -> i64 {
+        self.0.absenti
+    }
+
+    async fn appealed(&self) -> i64 {
+        self.0.contestatii
+    }
+
+    async fn admitted(&self) -> i64 {
+        self.0.admitere
+    }
+}
+
+pub fn schema(db: Arc<DB>, config: Arc<ServerConfig>) -> AdmSchema {
+    AdmSchema::build(Query, EmptyMutation, EmptySubscription)
+        .data(db)
+        .data(config)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+pub async fn handler(
+    Extension(schema): Extension<AdmSchema>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(req.into_inner()).await.into()
+}
+
+/// An interactive editor for trying out queries, served on `GET`.
+pub async fn playground(Extension(config): Extension<Arc<ServerConfig>>) -> impl IntoResponse {
+    let endpoint = format!("{}/graphql", config.v2_base_path());
+    Html(playground_source(GraphQLPlaygroundConfig::new(&endpoint)))
+}
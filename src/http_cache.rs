@@ -0,0 +1,77 @@
+use crate::config::ServerConfig;
+use crate::dbmgr::DB;
+use axum::extract::{Path, RequestParts};
+use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Weak, since compression changes the bytes but not the meaning of a response.
+fn etag(db_etag: &str) -> String {
+    // both renderings of missing values can sit behind the same CDN
+    let variant = if repartizare_api::compat::legacy_sentinels() {
+        "-legacy"
+    } else {
+        ""
+    };
+    format!("W/\"{db_etag}{variant}\"")
+}
+
+fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+fn into_request<B>(parts: RequestParts<B>) -> Request<B> {
+    parts
+        .try_into_request()
+        .expect("the body is never extracted")
+}
+
+/// Add `Cache-Control` and an `ETag` derived from the year's database file to successful
+/// responses, and answer `304 Not Modified` when the client already has the current version.
+pub async fn cache_headers<B: Send>(req: Request<B>, next: Next<B>) -> Response {
+    let mut parts = RequestParts::new(req);
+    let year: Option<i32> = parts
+        .extract::<Path<HashMap<String, String>>>()
+        .await
+        .ok()
+        .and_then(|params| params.get("year")?.parse().ok());
+    let config = parts.extensions().get::<Arc<ServerConfig>>().cloned();
+    let db = parts.extensions().get::<Arc<DB>>().cloned();
+
+    let etag = match (year, db) {
+        (Some(year), Some(db)) => match db.etag(year).await.ok() {
+            Some(tag) => Some(etag(&tag)),
+            // the handler will report the error, which must not be cached
+            None => return next.run(into_request(parts)).await,
+        },
+        _ => None,
+    };
+    let cache_control = config.map(|config| config.cache_control(year));
+
+    let mut headers = HeaderMap::new();
+    if let Some(val) = cache_control.and_then(|x| HeaderValue::from_str(&x).ok()) {
+        headers.insert(CACHE_CONTROL, val);
+    }
+    if let Some(val) = etag.as_ref().and_then(|x| HeaderValue::from_str(x).ok()) {
+        headers.insert(ETAG, val);
+    }
+
+    if matches!(&etag, Some(etag) if matches(parts.headers(), etag)) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    let mut res = next.run(into_request(parts)).await;
+    if res.status() == StatusCode::OK {
+        res.headers_mut().extend(headers);
+    }
+    res
+}
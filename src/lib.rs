@@ -1,9 +1,25 @@
+pub mod config;
 pub mod db;
 
+pub mod contestatie;
 pub mod county;
+pub mod evaluare;
+pub mod fetch;
+pub mod round;
+pub mod source;
 pub mod specializare;
 pub mod student;
 
+pub mod compat;
 pub mod dbmgr;
+pub mod graphql;
+pub mod http_cache;
+pub mod logging;
+pub mod metrics;
+pub mod openapi;
+pub mod ratelimit;
+pub mod repository;
 pub mod server;
+pub mod v2;
+pub mod verify;
 pub mod year_gen;
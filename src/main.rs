@@ -1,4 +1,11 @@
 use clap::{Parser, Subcommand};
+use repartizare_c8::config::ServerConfig;
+use repartizare_c8::db;
+use repartizare_c8::fetch::FetchOptions;
+use repartizare_c8::logging::LogFormat;
+use repartizare_c8::year_gen::IfExists;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -6,33 +13,175 @@ use clap::{Parser, Subcommand};
 struct Args {
     #[clap(subcommand)]
     command: Commands,
+    #[clap(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    #[clap(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
     Generator {
+        #[clap(subcommand)]
+        command: Option<Box<GeneratorCommands>>,
+        #[clap(required = true)]
+        year: Option<i32>,
+        /// Directory holding one {year}.db per year, shared with the server
+        #[clap(long, env = "DATA_DIR", default_value = "./")]
+        data_dir: PathBuf,
+        /// Write to this file instead of {data-dir}/{year}.db
+        #[clap(long)]
+        out: Option<PathBuf>,
+        /// What to do if the database already exists
+        #[clap(long, value_enum, default_value_t = IfExists::Update)]
+        if_exists: IfExists,
+        /// Only refresh this county code
+        #[clap(long)]
+        county: Option<String>,
+        /// Re-fetch everything, even files that haven't changed since the last run
+        #[clap(long)]
+        force: bool,
+        #[clap(flatten)]
+        fetch: FetchOptions,
+    },
+    Verify {
+        year: i32,
+        #[clap(long, env = "DATA_DIR", default_value = "./")]
+        data_dir: PathBuf,
+        /// Skip comparing the database against the ministry index
+        #[clap(long)]
+        offline: bool,
+        #[clap(flatten)]
+        fetch: FetchOptions,
+    },
+    /// Print how appeals changed admission averages and county ranks
+    Appeals {
         year: i32,
+        #[clap(long, env = "DATA_DIR", default_value = "./")]
+        data_dir: PathBuf,
+        #[clap(long)]
+        county: Option<String>,
     },
     Server {
-        #[clap(long, default_value_t = String::from("./"))]
-        path: String,
-        #[clap(short, long, default_value_t = 8095)]
-        port: u16,
+        /// TOML file with the server settings, overridden by ADM_* environment variables
+        #[clap(long, env = "ADM_CONFIG")]
+        config: Option<PathBuf>,
+        /// Directory holding one {year}.db per year, overriding ADM_DATA_DIR [default: ./]
+        #[clap(long)]
+        path: Option<PathBuf>,
+        /// Address to listen on [default: 0.0.0.0:8095]
+        #[clap(long)]
+        bind: Option<SocketAddr>,
+        /// Port to listen on, keeping the configured address
+        #[clap(short, long)]
+        port: Option<u16>,
+        /// Serialize missing values as -1 instead of null, like older releases did, unless a
+        /// request asks otherwise
+        #[clap(long)]
+        legacy_sentinels: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum GeneratorCommands {
+    /// Keep polling the ministry and apply newly published results as they appear
+    Watch {
+        #[clap(long)]
+        year: i32,
+        #[clap(long, env = "DATA_DIR", default_value = "./")]
+        data_dir: PathBuf,
+        /// Update this file instead of {data-dir}/{year}.db
+        #[clap(long)]
+        out: Option<PathBuf>,
+        /// Time between polls, e.g. 30s or 5m
+        #[clap(long, default_value = "5m")]
+        interval: humantime::Duration,
+        /// Only watch this county code
+        #[clap(long)]
+        county: Option<String>,
+        #[clap(flatten)]
+        fetch: FetchOptions,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Args::parse();
+    repartizare_c8::logging::init(cli.log_format);
 
     match cli.command {
-        Commands::Generator { year } => {
-            println!("Generating year {year}");
-            repartizare_c8::year_gen::do_year(year).await?;
+        Commands::Generator {
+            command: Some(command),
+            ..
+        } => {
+            let GeneratorCommands::Watch {
+                year,
+                data_dir,
+                out,
+                interval,
+                county,
+                fetch,
+            } = *command;
+            let target = out.unwrap_or_else(|| db::year_path(&data_dir, year));
+            repartizare_c8::year_gen::watch(year, &target, county, interval.into(), &fetch).await?;
+        }
+        Commands::Generator {
+            command: None,
+            year,
+            data_dir,
+            out,
+            if_exists,
+            county,
+            force,
+            fetch,
+        } => {
+            let year = year.expect("year is required without a subcommand");
+            let target = out.unwrap_or_else(|| db::year_path(&data_dir, year));
+            tracing::info!(year, path = %target.display(), "generating");
+            repartizare_c8::year_gen::do_year(year, &target, if_exists, county, force, &fetch)
+                .await?;
         }
-        Commands::Server { path, port } => {
-            println!("Starting server, listening on port {port}, serving from '{path}' ");
-            repartizare_c8::server::run_server(path, port).await?;
+        Commands::Verify {
+            year,
+            data_dir,
+            offline,
+            fetch,
+        } => {
+            println!("Verifying year {year}");
+            repartizare_c8::verify::verify_year(&data_dir, year, offline, &fetch).await?;
+        }
+        Commands::Appeals {
+            year,
+            data_dir,
+            county,
+        } => {
+            repartizare_c8::contestatie::report(&data_dir, year, county).await?;
+        }
+        Commands::Server {
+            config,
+            path,
+            bind,
+            port,
+            legacy_sentinels,
+        } => {
+            let mut config = ServerConfig::load(config.as_deref())?;
+            if let Some(path) = path {
+                config.data_dir = path;
+            }
+            if let Some(bind) = bind {
+                config.bind = bind;
+            }
+            if let Some(port) = port {
+                config.bind.set_port(port);
+            }
+            config.legacy_sentinels |= legacy_sentinels;
+
+            tracing::info!(
+                bind = %config.bind,
+                base_path = config.base_path.as_str(),
+                path = %config.data_dir.display(),
+                "starting server"
+            );
+            repartizare_c8::server::run_server(config).await?;
         }
     };
     Ok(())
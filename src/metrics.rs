@@ -0,0 +1,146 @@
+use crate::dbmgr::DB;
+use axum::extract::MatchedPath;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::Instant;
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    errors: IntCounterVec,
+    open_pools: IntGauge,
+    pool_connections: IntGaugeVec,
+    pool_max_connections: IntGauge,
+    cache_hits: IntCounter,
+    cache_misses: IntCounter,
+    cache_entries: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let metrics = Metrics {
+            registry: Registry::new_custom(Some("adm".to_string()), None).unwrap(),
+            requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "API requests by route and status"),
+                &["route", "status"],
+            )
+            .unwrap(),
+            latency: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "Time spent answering API requests",
+                ),
+                &["route"],
+            )
+            .unwrap(),
+            errors: IntCounterVec::new(
+                Opts::new("errors_total", "Error responses by kind"),
+                &["kind"],
+            )
+            .unwrap(),
+            open_pools: IntGauge::new("db_open_pools", "Year databases currently open").unwrap(),
+            pool_connections: IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Connections per year database"),
+                &["year", "state"],
+            )
+            .unwrap(),
+            pool_max_connections: IntGauge::new(
+                "db_pool_max_connections",
+                "Connection limit of every year database",
+            )
+            .unwrap(),
+            cache_hits: IntCounter::new("cache_hits_total", "Queries answered from the cache")
+                .unwrap(),
+            cache_misses: IntCounter::new("cache_misses_total", "Queries that hit SQLite").unwrap(),
+            cache_entries: IntGauge::new("cache_entries", "Results held in the cache").unwrap(),
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 9] = [
+            Box::new(metrics.requests.clone()),
+            Box::new(metrics.latency.clone()),
+            Box::new(metrics.errors.clone()),
+            Box::new(metrics.open_pools.clone()),
+            Box::new(metrics.pool_connections.clone()),
+            Box::new(metrics.pool_max_connections.clone()),
+            Box::new(metrics.cache_hits.clone()),
+            Box::new(metrics.cache_misses.clone()),
+            Box::new(metrics.cache_entries.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap();
+        }
+
+        metrics
+    }
+}
+
+/// Count an error response of the given kind.
+pub fn error(kind: &str) {
+    METRICS.errors.with_label_values(&[kind]).inc();
+}
+
+/// Record the count, status and latency of requests to a matched route.
+pub async fn track<B>(req: Request<B>, next: Next<B>) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unknown".to_string(), |path| path.as_str().to_string());
+    let started = Instant::now();
+
+    let res = next.run(req).await;
+
+    METRICS
+        .latency
+        .with_label_values(&[&route])
+        .observe(started.elapsed().as_secs_f64());
+    METRICS
+        .requests
+        .with_label_values(&[&route, res.status().as_str()])
+        .inc();
+    res
+}
+
+/// Refresh the gauges from `db` and render every metric in the Prometheus text format.
+pub async fn render(db: &DB) -> String {
+    let pools = db.pool_stats().await;
+    METRICS.open_pools.set(pools.len() as i64);
+    METRICS.pool_connections.reset();
+    for pool in pools {
+        let year = pool.year.to_string();
+        let idle = pool.idle as i64;
+        METRICS
+            .pool_connections
+            .with_label_values(&[&year, "idle"])
+            .set(idle);
+        METRICS
+            .pool_connections
+            .with_label_values(&[&year, "busy"])
+            .set(pool.size as i64 - idle);
+    }
+    METRICS.pool_max_connections.set(db.pool_size() as i64);
+
+    // the cache keeps its own totals, catch the counters up to them
+    let cache = db.cache_stats();
+    METRICS
+        .cache_hits
+        .inc_by(cache.hits.saturating_sub(METRICS.cache_hits.get()));
+    METRICS
+        .cache_misses
+        .inc_by(cache.misses.saturating_sub(METRICS.cache_misses.get()));
+    METRICS.cache_entries.set(cache.entries as i64);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .unwrap();
+    String::from_utf8(buffer).unwrap()
+}
@@ -0,0 +1,140 @@
+use crate::contestatie::Contestatie;
+use crate::county::County;
+use crate::dbmgr::{FullSchool, FullSpec, GradeBucket, Participation, SpecShort, Unplaced};
+use crate::round::Round;
+use crate::server;
+use crate::specializare::Specializare;
+use crate::student::{Placement, Student};
+use crate::v2;
+use serde::Serialize;
+use utoipa::openapi::server::Server;
+use utoipa::{OpenApi, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultType {
+    Success,
+    Error,
+}
+
+/// Every response is wrapped in this envelope. When `type` is `error`, `data` is a message
+/// instead, and the HTTP status may still be 200.
+#[derive(Serialize, ToSchema)]
+#[aliases(
+    YearsResponse = Envelope<Vec<i32>>,
+    CountiesResponse = Envelope<Vec<County>>,
+    SchoolsResponse = Envelope<Vec<String>>,
+    FullSchoolResponse = Envelope<FullSchool>,
+    UnplacedResponse = Envelope<Unplaced>,
+    ParticipationResponse = Envelope<Participation>,
+    AppealsResponse = Envelope<Vec<Contestatie>>,
+    ErrorResponse = Envelope<String>
+)]
+pub struct Envelope<T> {
+    #[serde(rename = "type")]
+    pub result_type: ResultType,
+    pub data: T,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Admitere liceu API",
+        description = "Results of the Romanian high school admission (repartizare computerizată) and of the Evaluare Națională, one database per year."
+    ),
+    paths(
+        server::years,
+        server::counties,
+        server::schools,
+        server::school,
+        server::unplaced,
+        server::participation,
+        server::appeals,
+    ),
+    components(schemas(
+        ResultType,
+        YearsResponse,
+        CountiesResponse,
+        SchoolsResponse,
+        FullSchoolResponse,
+        UnplacedResponse,
+        ParticipationResponse,
+        AppealsResponse,
+        ErrorResponse,
+        County,
+        Round,
+        Placement,
+        Student,
+        Specializare,
+        SpecShort,
+        FullSpec,
+        FullSchool,
+        GradeBucket,
+        Unplaced,
+        Participation,
+        Contestatie,
+    ))
+)]
+struct ApiDoc;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Admitere liceu API v2",
+        description = "The same data as the original API, with English field names, `null` for missing values and HTTP status codes for errors."
+    ),
+    paths(
+        v2::years,
+        v2::counties,
+        v2::schools,
+        v2::school,
+        v2::unplaced,
+        v2::participation,
+        v2::appeals,
+    ),
+    components(schemas(
+        v2::YearsData,
+        v2::CountiesData,
+        v2::SchoolsData,
+        v2::SchoolData,
+        v2::UnplacedData,
+        v2::ParticipationData,
+        v2::AppealsData,
+        v2::ErrorResponse,
+        v2::ErrorBody,
+        v2::CountyV2,
+        v2::SchoolSummary,
+        v2::Candidate,
+        v2::Program,
+        v2::School,
+        v2::AverageBucket,
+        v2::UnplacedV2,
+        v2::ParticipationV2,
+        v2::GradeChange,
+        v2::RankChange,
+        v2::AppealGrades,
+        v2::Appeal,
+        Round,
+        Placement,
+    ))
+)]
+struct ApiDocV2;
+
+fn with_server(mut doc: utoipa::openapi::OpenApi, base_path: &str) -> utoipa::openapi::OpenApi {
+    doc.servers = Some(vec![Server::new(if base_path.is_empty() {
+        "/"
+    } else {
+        base_path
+    })]);
+    doc
+}
+
+/// The OpenAPI document for the API mounted at `base_path`.
+pub fn document(base_path: &str) -> utoipa::openapi::OpenApi {
+    with_server(ApiDoc::openapi(), base_path)
+}
+
+/// The OpenAPI document for the second version, mounted at `base_path`.
+pub fn document_v2(base_path: &str) -> utoipa::openapi::OpenApi {
+    with_server(ApiDocV2::openapi(), base_path)
+}
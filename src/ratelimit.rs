@@ -0,0 +1,342 @@
+use crate::config::{Limit, RateLimitConfig};
+use axum::extract::{ConnectInfo, MatchedPath};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets kept at most. Past this the least recently seen client is forgotten, and starts over
+/// with a full bucket if it comes back.
+const MAX_BUCKETS: usize = 10_000;
+
+/// An address range, e.g. `10.0.0.0/8`. A plain address is a range of one.
+#[derive(Debug, Clone, Copy)]
+struct Cidr {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl Cidr {
+    fn parse(text: &str) -> Result<Cidr, String> {
+        let invalid = || format!("invalid trusted proxy {text}");
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Cidr { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+            ip => ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// Take a token, or say how long until one is available.
+    fn take(&mut self, limit: &Limit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limit.per_second,
+            ))
+        }
+    }
+}
+
+/// Per client IP token buckets, one for each route with its own limit and one shared by the rest.
+pub struct RateLimiter {
+    trusted: Vec<Cidr>,
+    default: Limit,
+    routes: HashMap<String, Limit>,
+    buckets: Mutex<lru::LruCache<(IpAddr, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Result<RateLimiter, String> {
+        RateLimiter::with_capacity(config, NonZeroUsize::new(MAX_BUCKETS).unwrap())
+    }
+
+    fn with_capacity(
+        config: &RateLimitConfig,
+        capacity: NonZeroUsize,
+    ) -> Result<RateLimiter, String> {
+        Ok(RateLimiter {
+            trusted: config
+                .trusted_proxies
+                .iter()
+                .map(|x| Cidr::parse(x))
+                .collect::<Result<_, _>>()?,
+            default: config.default,
+            routes: config.routes.clone(),
+            buckets: Mutex::new(lru::LruCache::new(capacity)),
+        })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|x| x.contains(ip))
+    }
+
+    /// The address of the client, found by walking `X-Forwarded-For` back from the connecting
+    /// peer for as long as the hops are trusted proxies.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+
+        let hops: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|val| val.to_str().ok())
+            .flat_map(|val| val.split(','))
+            .filter_map(|hop| hop.trim().parse().ok())
+            .collect();
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            client = hop;
+            if !self.is_trusted(hop) {
+                break;
+            }
+        }
+        client
+    }
+
+    fn check(&self, ip: IpAddr, route: &str) -> Result<(), Duration> {
+        let (key, limit) = match self.routes.get_key_value(route) {
+            Some((route, limit)) => (route.as_str(), limit),
+            None => ("default", &self.default),
+        };
+
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .unwrap()
+            .get_or_insert_mut((ip, key.to_string()), || Bucket {
+                tokens: limit.burst as f64,
+                updated: now,
+            })
+            .take(limit, now)
+    }
+}
+
+/// Name of a route, as used in the `routes` config: its last fixed path segment.
+fn route_name(path: &str) -> &str {
+    // v2 has a single school under `schools`, limited like the v1 route it replaces
+    if path.ends_with("/schools/:school") {
+        return "fullSchool";
+    }
+    path.rsplit('/')
+        .find(|x| !x.is_empty() && !x.starts_with(':'))
+        .unwrap_or(path)
+}
+
+/// Answer `429 Too Many Requests` to clients over their limit for the matched route.
+pub async fn limit<B>(req: Request<B>, next: Next<B>) -> Response {
+    let limiter = req.extensions().get::<Arc<RateLimiter>>().cloned();
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| route_name(path.as_str()).to_string());
+
+    if let (Some(limiter), Some(peer), Some(route)) = (limiter, peer, route) {
+        let ip = limiter.client_ip(peer, req.headers());
+        if let Err(wait) = limiter.check(ip, &route) {
+            tracing::debug!(%ip, route = route.as_str(), "rate limited");
+            crate::metrics::error("rate_limited");
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let retry_after = [(RETRY_AFTER, retry_after.to_string())];
+            if req.extensions().get::<crate::v2::Version2>().is_some() {
+                return (retry_after, crate::v2::too_many_requests().body()).into_response();
+            }
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                retry_after,
+                crate::server::error::<()>("429 Too Many Requests".to_string()),
+            )
+                .into_response();
+        }
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{Extension, Router};
+    use tower::ServiceExt;
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    fn config(trusted: &[&str]) -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            trusted_proxies: trusted.iter().map(|x| x.to_string()).collect(),
+            default: Limit {
+                per_second: 1.0,
+                burst: 2,
+            },
+            routes: HashMap::new(),
+        }
+    }
+
+    fn forwarded(hops: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", hops.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn cidr_contains() {
+        let net = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains(ip("10.1.255.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+
+        let single = Cidr::parse("192.168.0.7").unwrap();
+        assert!(single.contains(ip("192.168.0.7")));
+        assert!(!single.contains(ip("192.168.0.8")));
+
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(Cidr::parse("fd00::/8").unwrap().contains(ip("fd12::1")));
+        assert!(!Cidr::parse("fd00::/8").unwrap().contains(ip("10.1.0.1")));
+    }
+
+    #[test]
+    fn v4_mapped_addresses_match_v4_ranges() {
+        let net = Cidr::parse("10.1.0.0/16").unwrap();
+
+        assert!(net.contains(ip("::ffff:10.1.2.3")));
+        assert!(!net.contains(ip("::ffff:10.2.2.3")));
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("fd00::/129").is_err());
+        assert!(Cidr::parse("proxy").is_err());
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let limiter = RateLimiter::new(&config(&["10.0.0.0/8"])).unwrap();
+
+        let client = limiter.client_ip(ip("203.0.113.9"), &forwarded("198.51.100.1"));
+
+        assert_eq!(client, ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn forwarded_for_is_walked_through_trusted_proxies() {
+        let limiter = RateLimiter::new(&config(&["10.0.0.0/8"])).unwrap();
+
+        // the client claims to be 1.1.1.1, but only 198.51.100.1 was seen by a trusted proxy
+        let headers = forwarded("1.1.1.1, 198.51.100.1, 10.0.0.2");
+        let client = limiter.client_ip(ip("10.0.0.1"), &headers);
+
+        assert_eq!(client, ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn burst_then_limited() {
+        let limiter = RateLimiter::new(&config(&[])).unwrap();
+        let client = ip("198.51.100.1");
+
+        assert!(limiter.check(client, "counties").is_ok());
+        assert!(limiter.check(client, "counties").is_ok());
+        let wait = limiter.check(client, "counties").unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+        // other clients have buckets of their own
+        assert!(limiter.check(ip("198.51.100.2"), "counties").is_ok());
+    }
+
+    #[test]
+    fn buckets_are_capped() {
+        let limiter =
+            RateLimiter::with_capacity(&config(&[]), NonZeroUsize::new(2).unwrap()).unwrap();
+
+        for last in 1..=5 {
+            let client = ip(&format!("198.51.100.{last}"));
+            limiter.check(client, "counties").unwrap();
+            limiter.check(client, "counties").unwrap();
+        }
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert!(buckets.contains(&(ip("198.51.100.5"), "default".to_string())));
+        assert!(!buckets.contains(&(ip("198.51.100.1"), "default".to_string())));
+    }
+
+    #[tokio::test]
+    async fn limited_requests_get_retry_after() {
+        let limiter = Arc::new(RateLimiter::new(&config(&[])).unwrap());
+        let app = Router::new()
+            .route("/:year/counties", get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn(limit))
+            .layer(Extension(limiter));
+        let request = || {
+            let mut req = Request::get("/2023/counties").body(Body::empty()).unwrap();
+            req.extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 1], 4000))));
+            req
+        };
+
+        for _ in 0..2 {
+            let res = app.clone().oneshot(request()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+        let res = app.clone().oneshot(request()).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[RETRY_AFTER], "1");
+    }
+}
@@ -0,0 +1,594 @@
+use crate::county::{self, County};
+use crate::round::Round;
+use crate::specializare::{self, Specializare};
+use crate::student::{self, Placement, Student};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use std::collections::HashMap;
+use std::future::Future;
+use tokio::sync::RwLock;
+
+/// Why a [`Repository`] query failed.
+#[derive(Debug)]
+pub enum Error {
+    /// There is no data for this year.
+    UnknownYear(i32),
+    /// Every connection to the year's database stayed busy until the pool gave up waiting.
+    Busy,
+    Database(sqlx::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownYear(year) => write!(f, "no data for {year}"),
+            Error::Busy => write!(f, "database busy, try again later"),
+            Error::Database(err) => write!(f, "database error: {err}"),
+            Error::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Error {
+        match err {
+            sqlx::Error::PoolTimedOut => Error::Busy,
+            err => Error::Database(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+/// Counties to return, all of them by default.
+#[derive(Debug, Clone, Default)]
+pub struct CountyFilter {
+    /// Only these codes, e.g. `B` or `CJ`.
+    pub codes: Vec<String>,
+}
+
+impl CountyFilter {
+    pub fn new() -> CountyFilter {
+        CountyFilter::default()
+    }
+
+    pub fn code(mut self, code: impl Into<String>) -> CountyFilter {
+        self.codes.push(code.into());
+        self
+    }
+}
+
+/// A high school, as named by the ministry in a county.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct School {
+    pub judet: String,
+    pub liceu: String,
+}
+
+/// High schools to return: those with seats in `round`, in every county by default.
+#[derive(Debug, Clone, Default)]
+pub struct SchoolFilter {
+    pub round: Round,
+    pub county: Option<String>,
+    /// Only names containing this, ignoring ASCII case.
+    pub name: Option<String>,
+}
+
+impl SchoolFilter {
+    pub fn new() -> SchoolFilter {
+        SchoolFilter::default()
+    }
+
+    pub fn round(mut self, round: Round) -> SchoolFilter {
+        self.round = round;
+        self
+    }
+
+    pub fn county(mut self, county: impl Into<String>) -> SchoolFilter {
+        self.county = Some(county.into());
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> SchoolFilter {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// Specializations to return: those of `round`, in every county and school by default.
+#[derive(Debug, Clone, Default)]
+pub struct SpecializationFilter {
+    pub round: Round,
+    pub county: Option<String>,
+    /// Exact name of the high school.
+    pub school: Option<String>,
+    /// Ids are only unique within a county.
+    pub id: Option<i32>,
+    pub bilingual: Option<bool>,
+    /// E.g. `Real` or `Uman`, ignoring ASCII case.
+    pub profile: Option<String>,
+}
+
+impl SpecializationFilter {
+    pub fn new() -> SpecializationFilter {
+        SpecializationFilter::default()
+    }
+
+    pub fn round(mut self, round: Round) -> SpecializationFilter {
+        self.round = round;
+        self
+    }
+
+    pub fn county(mut self, county: impl Into<String>) -> SpecializationFilter {
+        self.county = Some(county.into());
+        self
+    }
+
+    pub fn school(mut self, school: impl Into<String>) -> SpecializationFilter {
+        self.school = Some(school.into());
+        self
+    }
+
+    pub fn id(mut self, id: i32) -> SpecializationFilter {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn bilingual(mut self, bilingual: bool) -> SpecializationFilter {
+        self.bilingual = Some(bilingual);
+        self
+    }
+
+    pub fn profile(mut self, profile: impl Into<String>) -> SpecializationFilter {
+        self.profile = Some(profile.into());
+        self
+    }
+}
+
+/// Candidates to return: everyone in `round` by default, best admission average first.
+#[derive(Debug, Clone, Default)]
+pub struct StudentFilter {
+    pub round: Round,
+    pub county: Option<String>,
+    /// Exact name of the high school the candidate was placed in.
+    pub school: Option<String>,
+    /// Id of the specialization the candidate was placed in, only unique within a county.
+    pub specialization: Option<i32>,
+    pub status: Option<Placement>,
+    /// Inclusive bounds on the admission average. Candidates without one never match.
+    pub min_average: Option<f64>,
+    pub max_average: Option<f64>,
+    pub limit: Option<u32>,
+}
+
+impl StudentFilter {
+    pub fn new() -> StudentFilter {
+        StudentFilter::default()
+    }
+
+    pub fn round(mut self, round: Round) -> StudentFilter {
+        self.round = round;
+        self
+    }
+
+    pub fn county(mut self, county: impl Into<String>) -> StudentFilter {
+        self.county = Some(county.into());
+        self
+    }
+
+    pub fn school(mut self, school: impl Into<String>) -> StudentFilter {
+        self.school = Some(school.into());
+        self
+    }
+
+    pub fn specialization(mut self, id: i32) -> StudentFilter {
+        self.specialization = Some(id);
+        self
+    }
+
+    pub fn status(mut self, status: Placement) -> StudentFilter {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn min_average(mut self, average: f64) -> StudentFilter {
+        self.min_average = Some(average);
+        self
+    }
+
+    pub fn max_average(mut self, average: f64) -> StudentFilter {
+        self.max_average = Some(average);
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> StudentFilter {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Read access to the results of every year, independent of how they are stored or served.
+///
+/// [`crate::dbmgr::DB`] reads the databases written by the generator, [`MemoryRepository`]
+/// holds fixtures for tests.
+pub trait Repository: Send + Sync {
+    /// Years with data, in ascending order.
+    fn years(&self) -> impl Future<Output = Result<Vec<i32>, Error>> + Send;
+
+    /// Counties, by code.
+    fn counties(
+        &self,
+        year: i32,
+        filter: &CountyFilter,
+    ) -> impl Future<Output = Result<Vec<County>, Error>> + Send;
+
+    /// High schools, by county and name.
+    fn schools(
+        &self,
+        year: i32,
+        filter: &SchoolFilter,
+    ) -> impl Future<Output = Result<Vec<School>, Error>> + Send;
+
+    /// Specializations, by county, school and id.
+    fn specializations(
+        &self,
+        year: i32,
+        filter: &SpecializationFilter,
+    ) -> impl Future<Output = Result<Vec<Specializare>, Error>> + Send;
+
+    /// Candidates, best admission average first.
+    fn students(
+        &self,
+        year: i32,
+        filter: &StudentFilter,
+    ) -> impl Future<Output = Result<Vec<Student>, Error>> + Send;
+}
+
+/// The queries behind every [`Repository`] kept in SQLite, for one year's database.
+pub(crate) mod sql {
+    use super::*;
+
+    pub async fn counties(db: &SqlitePool, filter: &CountyFilter) -> Result<Vec<County>, Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM counties");
+        if !filter.codes.is_empty() {
+            query.push(" WHERE code IN (");
+            let mut codes = query.separated(", ");
+            for code in &filter.codes {
+                codes.push_bind(code);
+            }
+            query.push(")");
+        }
+        query.push(" ORDER BY code ASC");
+        Ok(query.build_query_as().fetch_all(db).await?)
+    }
+
+    pub async fn schools(db: &SqlitePool, filter: &SchoolFilter) -> Result<Vec<School>, Error> {
+        let mut query =
+            QueryBuilder::<Sqlite>::new("SELECT judet, liceu FROM specializari WHERE round = ");
+        query.push_bind(filter.round);
+        if let Some(county) = &filter.county {
+            query.push(" AND judet = ").push_bind(county);
+        }
+        if let Some(name) = &filter.name {
+            query
+                .push(" AND instr(lower(liceu), lower(")
+                .push_bind(name)
+                .push(")) > 0");
+        }
+        query.push(" GROUP BY judet, liceu ORDER BY judet ASC, liceu ASC");
+        Ok(query.build_query_as().fetch_all(db).await?)
+    }
+
+    pub async fn specializations(
+        db: &SqlitePool,
+        filter: &SpecializationFilter,
+    ) -> Result<Vec<Specializare>, Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM specializari WHERE round = ");
+        query.push_bind(filter.round);
+        if let Some(county) = &filter.county {
+            query.push(" AND judet = ").push_bind(county);
+        }
+        if let Some(school) = &filter.school {
+            query.push(" AND liceu = ").push_bind(school);
+        }
+        if let Some(id) = filter.id {
+            query.push(" AND id = ").push_bind(id);
+        }
+        if let Some(bilingual) = filter.bilingual {
+            query.push(" AND bilingv = ").push_bind(bilingual);
+        }
+        if let Some(profile) = &filter.profile {
+            query
+                .push(" AND lower(profil) = lower(")
+                .push_bind(profile)
+                .push(")");
+        }
+        query.push(" ORDER BY judet ASC, liceu ASC, id ASC");
+        Ok(query.build_query_as().fetch_all(db).await?)
+    }
+
+    pub async fn students(db: &SqlitePool, filter: &StudentFilter) -> Result<Vec<Student>, Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM students WHERE round = ");
+        query.push_bind(filter.round);
+        if let Some(county) = &filter.county {
+            query.push(" AND judet = ").push_bind(county);
+        }
+        if let Some(school) = &filter.school {
+            query.push(" AND liceu = ").push_bind(school);
+        }
+        if let Some(id) = filter.specialization {
+            query.push(" AND id_specializare = ").push_bind(id);
+        }
+        if let Some(status) = filter.status {
+            query.push(" AND status = ").push_bind(status);
+        }
+        if let Some(min) = filter.min_average {
+            query.push(" AND medie_adm >= ").push_bind(min);
+        }
+        if let Some(max) = filter.max_average {
+            query.push(" AND medie_adm <= ").push_bind(max);
+        }
+        query.push(" ORDER BY medie_adm DESC");
+        if let Some(limit) = filter.limit {
+            query.push(" LIMIT ").push_bind(limit);
+        }
+        Ok(query.build_query_as().fetch_all(db).await?)
+    }
+}
+
+/// A [`Repository`] over in-memory SQLite databases, filled by the caller. Counties have to be
+/// inserted before the specializations and candidates that refer to them.
+#[derive(Default)]
+pub struct MemoryRepository {
+    years: RwLock<HashMap<i32, SqlitePool>>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> MemoryRepository {
+        MemoryRepository::default()
+    }
+
+    /// `year`'s database, created empty the first time.
+    pub async fn pool(&self, year: i32) -> Result<SqlitePool, Error> {
+        if let Some(pool) = self.years.read().await.get(&year) {
+            return Ok(pool.clone());
+        }
+        let mut years = self.years.write().await;
+        if let Some(pool) = years.get(&year) {
+            return Ok(pool.clone());
+        }
+        let pool = crate::db::memory_pool().await?;
+        years.insert(year, pool.clone());
+        Ok(pool)
+    }
+
+    async fn existing(&self, year: i32) -> Result<SqlitePool, Error> {
+        self.years
+            .read()
+            .await
+            .get(&year)
+            .cloned()
+            .ok_or(Error::UnknownYear(year))
+    }
+
+    pub async fn insert_counties(&self, year: i32, counties: &[County]) -> Result<(), Error> {
+        let pool = self.pool(year).await?;
+        for county in counties {
+            county::insert_county(county, &pool).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn insert_specializari(
+        &self,
+        year: i32,
+        specializari: &[Specializare],
+    ) -> Result<(), Error> {
+        let mut conn = self.pool(year).await?.acquire().await?;
+        for sp in specializari {
+            specializare::insert_specializare(sp, &mut conn).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn insert_students(&self, year: i32, students: &[Student]) -> Result<(), Error> {
+        let mut conn = self.pool(year).await?.acquire().await?;
+        for st in students {
+            student::insert_student(st, &mut conn).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Repository for MemoryRepository {
+    async fn years(&self) -> Result<Vec<i32>, Error> {
+        let mut years: Vec<i32> = self.years.read().await.keys().copied().collect();
+        years.sort_unstable();
+        Ok(years)
+    }
+
+    async fn counties(&self, year: i32, filter: &CountyFilter) -> Result<Vec<County>, Error> {
+        sql::counties(&self.existing(year).await?, filter).await
+    }
+
+    async fn schools(&self, year: i32, filter: &SchoolFilter) -> Result<Vec<School>, Error> {
+        sql::schools(&self.existing(year).await?, filter).await
+    }
+
+    async fn specializations(
+        &self,
+        year: i32,
+        filter: &SpecializationFilter,
+    ) -> Result<Vec<Specializare>, Error> {
+        sql::specializations(&self.existing(year).await?, filter).await
+    }
+
+    async fn students(&self, year: i32, filter: &StudentFilter) -> Result<Vec<Student>, Error> {
+        sql::students(&self.existing(year).await?, filter).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn county(code: &str) -> County {
+        County {
+            id: county::official_id(code),
+            code: code.to_string(),
+            name: code.to_string(),
+        }
+    }
+
+    fn spec(judet: &str, id: i32, profil: &str, bilingv: bool) -> Specializare {
+        Specializare {
+            id,
+            round: Round::First,
+            name: format!("{id}: Specializare"),
+            judet: judet.to_string(),
+            liceu: "Liceu".to_string(),
+            mediu: "Urban".to_string(),
+            specializare: "Specializare".to_string(),
+            bilingv,
+            locuri: Some(28),
+            ocupate: Some(1),
+            profil: profil.to_string(),
+            filiera: "Teoretica".to_string(),
+            ultima_medie: None,
+            ultima_medie_anterior: None,
+        }
+    }
+
+    fn student(id: &str, medie: Option<f64>, status: Placement) -> Student {
+        Student {
+            id: id.to_string(),
+            round: Round::First,
+            provenienta: "Scoala".to_string(),
+            judet: "AB".to_string(),
+            medie_admitere: medie,
+            medie_evaluare: medie,
+            medie_absolvire: medie,
+            nota_romana: None,
+            nota_mate: None,
+            liceu: "Liceu".to_string(),
+            id_specializare: (status != Placement::Unplaced).then_some(1),
+            status,
+            specializare: String::new(),
+        }
+    }
+
+    async fn repository() -> MemoryRepository {
+        let repo = MemoryRepository::new();
+        repo.insert_counties(2023, &[county("AB"), county("CV")])
+            .await
+            .unwrap();
+        repo.insert_specializari(
+            2023,
+            &[
+                spec("AB", 1, "Real", false),
+                spec("AB", 2, "Uman", true),
+                spec("CV", 1, "Real", true),
+            ],
+        )
+        .await
+        .unwrap();
+        repo.insert_students(
+            2023,
+            &[
+                student("AB1", Some(9.5), Placement::Placed),
+                student("AB2", Some(8.0), Placement::Placed),
+                student("AB3", Some(7.0), Placement::Unplaced),
+                student("AB4", None, Placement::Unplaced),
+            ],
+        )
+        .await
+        .unwrap();
+        repo
+    }
+
+    fn ids(students: &[Student]) -> Vec<&str> {
+        students.iter().map(|x| x.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn students_by_status() {
+        let repo = repository().await;
+
+        let filter = StudentFilter::new().status(Placement::Unplaced);
+        let students = repo.students(2023, &filter).await.unwrap();
+
+        assert_eq!(ids(&students), ["AB3", "AB4"]);
+    }
+
+    #[tokio::test]
+    async fn average_bounds_are_inclusive() {
+        let repo = repository().await;
+
+        let filter = StudentFilter::new().min_average(7.0).max_average(9.5);
+        assert_eq!(
+            ids(&repo.students(2023, &filter).await.unwrap()),
+            ["AB1", "AB2", "AB3"]
+        );
+
+        let filter = StudentFilter::new().min_average(8.5);
+        assert_eq!(ids(&repo.students(2023, &filter).await.unwrap()), ["AB1"]);
+
+        // AB4 has no average, so no bound matches it
+        let filter = StudentFilter::new().max_average(7.5);
+        assert_eq!(ids(&repo.students(2023, &filter).await.unwrap()), ["AB3"]);
+    }
+
+    #[tokio::test]
+    async fn limit_keeps_the_best_averages() {
+        let repo = repository().await;
+
+        let filter = StudentFilter::new().limit(2);
+        let students = repo.students(2023, &filter).await.unwrap();
+
+        assert_eq!(ids(&students), ["AB1", "AB2"]);
+    }
+
+    #[tokio::test]
+    async fn specializations_by_profile_ignore_case() {
+        let repo = repository().await;
+
+        let filter = SpecializationFilter::new().profile("real");
+        let specs = repo.specializations(2023, &filter).await.unwrap();
+        let found: Vec<_> = specs.iter().map(|x| (x.judet.as_str(), x.id)).collect();
+        assert_eq!(found, [("AB", 1), ("CV", 1)]);
+
+        let filter = SpecializationFilter::new()
+            .county("AB")
+            .profile("REAL")
+            .bilingual(true);
+        assert!(repo
+            .specializations(2023, &filter)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_year() {
+        let repo = repository().await;
+
+        let err = repo.counties(2019, &CountyFilter::new()).await.unwrap_err();
+
+        assert!(matches!(err, Error::UnknownYear(2019)));
+        assert_eq!(repo.years().await.unwrap(), [2023]);
+    }
+}
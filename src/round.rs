@@ -0,0 +1,15 @@
+pub use repartizare_api::Round;
+
+fn path(round: Round) -> &'static str {
+    match round {
+        Round::First => "repartizare",
+        Round::Second => "repartizare2",
+        Round::Special => "repartizare_locuri_speciale",
+    }
+}
+
+/// Address of a file the ministry publishes for `round`, under `base` (see
+/// [`crate::fetch::Fetcher::admitere_url`]).
+pub fn url(base: &str, round: Round, year: i32, file: &str) -> String {
+    format!("{base}/{year}/{}/{file}", path(round))
+}
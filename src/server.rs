@@ -1,115 +1,518 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use crate::config::ServerConfig;
+use crate::contestatie::Contestatie;
 use crate::county::County;
-use crate::dbmgr::{FullSchool, DB};
+use crate::dbmgr::{FullSchool, Participation, RowCounts, Unplaced, DB};
+use crate::metrics;
+use crate::ratelimit::RateLimiter;
+use crate::repository;
+use crate::round::Round;
+use axum::extract::Query;
 use axum::handler::Handler;
-use axum::response::IntoResponse;
+use axum::http::header::{CONTENT_TYPE, ETAG, VARY};
+use axum::http::{HeaderValue, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
 use axum::{extract::Path, response::Json, routing::get, Extension, Router};
-use serde::ser::SerializeMap;
-use serde::Serialize;
+use repartizare_api::Status;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::set_header::SetResponseHeaderLayer;
+use utoipa::IntoParams;
 
-const AVAILABLE_YEARS: [i32; 4] = [2020, 2021, 2022, 2023];
-
-async fn years() -> Json<Status<Vec<i32>>> {
-    Status::success(Vec::from(AVAILABLE_YEARS))
+#[utoipa::path(
+    get,
+    path = "/years",
+    responses(
+        (status = 200, description = "Years with published results", body = YearsResponse),
+    )
+)]
+pub(crate) async fn years(
+    Extension(config): Extension<Arc<ServerConfig>>,
+) -> Json<Status<Vec<i32>>> {
+    success(config.years.clone())
 }
 
-async fn counties(
+#[utoipa::path(
+    get,
+    path = "/{year}/counties",
+    params(("year" = i32, Path, description = "Year of the admission")),
+    responses(
+        (status = 200, description = "Counties, by code", body = CountiesResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn counties(
     Extension(db): Extension<Arc<DB>>,
     Path(year): Path<i32>,
-) -> Json<Status<Vec<County>>> {
+) -> Json<Status<Arc<Vec<County>>>> {
     match db.get_counties(year).await {
-        Ok(counties) => Status::success(counties),
-        Err(err) => Status::error(err.to_string()),
+        Ok(counties) => success(counties),
+        Err(err) => failed(err),
     }
 }
 
-async fn schools(
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct RoundQuery {
+    /// 1 for the first repartizare (default), 2 for the second, 3 for the special seats
+    // kept as text so a malformed value gets the error envelope instead of axum's plain 400
+    #[param(value_type = Option<i32>)]
+    round: Option<String>,
+}
+
+impl RoundQuery {
+    fn round(&self) -> Result<Round, String> {
+        self.round
+            .as_deref()
+            .map_or(Ok(Round::default()), str::parse)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/{county}/schools",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`"),
+        RoundQuery),
+    responses(
+        (status = 200, description = "Names of the high schools with seats in the round", body = SchoolsResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn schools(
     Extension(db): Extension<Arc<DB>>,
     Path((year, county)): Path<(i32, String)>,
-) -> Json<Status<Vec<String>>> {
-    match db.get_schools(year, county.as_str()).await {
-        Ok(schools) => Status::success(schools),
-        Err(err) => Status::error(err.to_string()),
+    Query(query): Query<RoundQuery>,
+) -> Json<Status<Arc<Vec<String>>>> {
+    let round = match query.round() {
+        Ok(round) => round,
+        Err(err) => return bad_request(err),
+    };
+    match db.get_schools(year, round, county.as_str()).await {
+        Ok(schools) => success(schools),
+        Err(err) => failed(err),
     }
 }
 
-async fn school(
+#[utoipa::path(
+    get,
+    path = "/{year}/{county}/fullSchool/{school}",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`"),
+        ("school" = String, Path, description = "High school name, as listed by `schools`"),
+        RoundQuery),
+    responses(
+        (status = 200, description = "Every specialization of the school, with the candidates placed in it", body = FullSchoolResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn school(
     Extension(db): Extension<Arc<DB>>,
     Path((year, county, school)): Path<(i32, String, String)>,
-) -> Json<Status<FullSchool>> {
+    Query(query): Query<RoundQuery>,
+) -> Json<Status<Arc<FullSchool>>> {
+    let round = match query.round() {
+        Ok(round) => round,
+        Err(err) => return bad_request(err),
+    };
     match db
-        .get_full_school(year, county.as_str(), school.as_str())
+        .get_full_school(year, round, county.as_str(), school.as_str())
         .await
     {
-        Ok(school) => Status::success(school),
-        Err(err) => Status::error(err.to_string()),
+        Ok(school) => success(school),
+        Err(err) => failed(err),
     }
 }
 
-async fn callback() -> impl IntoResponse {
-    (
-        axum::http::StatusCode::NOT_FOUND,
-        Status::<()>::error("404 Not Found".to_string()),
+#[utoipa::path(
+    get,
+    path = "/{year}/{county}/unplaced",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`"),
+        RoundQuery),
+    responses(
+        (status = 200, description = "Candidates left without a seat, and how their averages are distributed", body = UnplacedResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
     )
+)]
+pub(crate) async fn unplaced(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+    Query(query): Query<RoundQuery>,
+) -> Json<Status<Unplaced>> {
+    let round = match query.round() {
+        Ok(round) => round,
+        Err(err) => return bad_request(err),
+    };
+    match db.get_unplaced(year, round, county.as_str()).await {
+        Ok(unplaced) => success(unplaced),
+        Err(err) => failed(err),
+    }
 }
 
-pub async fn run_server(db_prefix: String, port: u16) -> Result<(), Box<dyn std::error::Error>> {
-    let app = Router::new()
-        .route("/adm_api/years", get(years))
-        .route("/adm_api/:year/counties", get(counties))
-        .route("/adm_api/:year/:county/schools", get(schools))
-        .route("/adm_api/:year/:county/fullSchool/:school", get(school))
-        .layer(Extension(Arc::new(DB::new(db_prefix))))
-        .fallback(callback.into_service());
+#[utoipa::path(
+    get,
+    path = "/{year}/{county}/participation",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`")),
+    responses(
+        (status = 200, description = "Evaluare Națională attendance and appeals", body = ParticipationResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn participation(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+) -> Json<Status<Participation>> {
+    match db.get_participation(year, county.as_str()).await {
+        Ok(participation) => success(participation),
+        Err(err) => failed(err),
+    }
+}
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await?;
+#[utoipa::path(
+    get,
+    path = "/{year}/{county}/appeals",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`")),
+    responses(
+        (status = 200, description = "Candidates whose grades changed after appeal", body = AppealsResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn appeals(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+) -> Json<Status<Vec<Contestatie>>> {
+    match db.get_appeals(year, county.as_str()).await {
+        Ok(appeals) => success(appeals),
+        Err(err) => failed(err),
+    }
+}
 
-    Ok(())
+async fn openapi(Extension(config): Extension<Arc<ServerConfig>>) -> impl IntoResponse {
+    Json(crate::openapi::document(config.base_path()))
 }
 
-impl Serialize for County {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut map = serializer.serialize_map(Some(2))?;
-        map.serialize_entry("code", self.code.as_str())?;
-        map.serialize_entry("name", self.name.as_str())?;
-        map.end()
+async fn openapi_v2(Extension(config): Extension<Arc<ServerConfig>>) -> impl IntoResponse {
+    Json(crate::openapi::document_v2(config.v2_base_path()))
+}
+
+/// Swagger UI settings, listing the documents of both API versions.
+fn swagger_config(config: &ServerConfig) -> utoipa_swagger_ui::Config<'static> {
+    // built once per app, so leaking the two urls does not grow over time
+    let url = |base_path: &str| -> &'static str {
+        Box::leak(format!("{base_path}/openapi.json").into_boxed_str())
+    };
+    utoipa_swagger_ui::Config::new([
+        utoipa_swagger_ui::Url::new("v1", url(config.base_path())),
+        utoipa_swagger_ui::Url::new("v2", url(config.v2_base_path())),
+    ])
+}
+
+/// The bundled Swagger UI, reading the documents served next to the APIs.
+async fn docs(
+    Extension(ui): Extension<Arc<utoipa_swagger_ui::Config<'static>>>,
+    tail: Option<Path<String>>,
+) -> Response {
+    let tail = tail.map_or(String::new(), |Path(tail)| tail);
+    match utoipa_swagger_ui::serve(tail.trim_start_matches('/'), ui) {
+        Ok(Some(file)) => {
+            ([(CONTENT_TYPE, file.content_type)], file.bytes.into_owned()).into_response()
+        }
+        Ok(None) => not_found(),
+        Err(err) => failed::<()>(err).into_response(),
     }
 }
 
+async fn healthz() -> Json<Status<&'static str>> {
+    success("ok")
+}
+
 #[derive(Serialize)]
-#[serde(untagged)]
-enum StatusData<T> {
-    Success(T),
-    Error(String),
+struct YearReadiness {
+    year: i32,
+    ready: bool,
+    #[serde(flatten)]
+    counts: Option<RowCounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
 #[derive(Serialize)]
-struct Status<T> {
-    #[serde(rename = "type")]
-    result_type: String,
-    data: StatusData<T>,
+struct Readiness {
+    ready: bool,
+    years: Vec<YearReadiness>,
 }
 
-impl<T> Status<T> {
-    fn success(data: T) -> Json<Status<T>> {
-        Json(Status {
-            result_type: "success".to_string(),
-            data: StatusData::Success(data),
-        })
+/// Ready once every configured year, and any other year found in the data directory, opens and
+/// has counties in it. Answers 503 otherwise, so no traffic is sent to an instance without data.
+async fn readyz(
+    Extension(db): Extension<Arc<DB>>,
+    Extension(config): Extension<Arc<ServerConfig>>,
+) -> impl IntoResponse {
+    let mut years = config.years.clone();
+    match db.discover_years() {
+        Ok(found) => years.extend(found),
+        Err(err) => tracing::warn!(error = %err, "cannot list the data directory"),
     }
+    years.sort_unstable();
+    years.dedup();
 
-    fn error(data: String) -> Json<Status<T>> {
-        Json(Status {
-            result_type: "error".to_string(),
-            data: StatusData::Error(data),
-        })
+    let mut report = Vec::new();
+    for year in years {
+        let (counts, error) = match db.count_rows(year).await {
+            Ok(counts) => (Some(counts), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        report.push(YearReadiness {
+            year,
+            ready: matches!(&counts, Some(counts) if counts.counties > 0),
+            counts,
+            error,
+        });
     }
+
+    let ready = report.iter().all(|x| x.ready);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    // a probe body rather than API data, so it is not wrapped in the Status envelope
+    (
+        status,
+        Json(Readiness {
+            ready,
+            years: report,
+        }),
+    )
+}
+
+async fn metrics(Extension(db): Extension<Arc<DB>>) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics::render(&db).await,
+    )
+}
+
+fn not_found() -> Response {
+    metrics::error("not_found");
+    (
+        StatusCode::NOT_FOUND,
+        error::<()>("404 Not Found".to_string()),
+    )
+        .into_response()
+}
+
+async fn callback(uri: Uri, Extension(config): Extension<Arc<ServerConfig>>) -> Response {
+    // nested routers cannot have their own fallback in this axum version
+    let v2 = config.v2_base_path();
+    match uri.path().strip_prefix(v2) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            crate::v2::not_found().into_response()
+        }
+        _ => not_found(),
+    }
+}
+
+/// Allow browsers on `cors_origins` to read the API. No origins means no CORS headers at all.
+fn cors(config: &ServerConfig) -> Result<CorsLayer, Box<dyn std::error::Error>> {
+    let origins = if config.cors_origins.iter().any(|x| x == "*") {
+        AllowOrigin::any()
+    } else {
+        let mut origins = Vec::new();
+        for origin in &config.cors_origins {
+            origins.push(HeaderValue::from_str(origin)?);
+        }
+        AllowOrigin::list(origins)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(origins)
+        // POST and Content-Type are for GraphQL queries
+        .allow_methods([Method::GET, Method::HEAD, Method::POST])
+        .allow_headers([CONTENT_TYPE])
+        .expose_headers([ETAG])
+        .max_age(Duration::from_secs(3600)))
+}
+
+/// Caching headers, rate limiting and metrics, for routes that serve data.
+fn track(router: Router) -> Router {
+    router
+        .route_layer(axum::middleware::from_fn(crate::http_cache::cache_headers))
+        .route_layer(axum::middleware::from_fn(crate::ratelimit::limit))
+        .route_layer(axum::middleware::from_fn(metrics::track))
+}
+
+/// Every route of the server, reading from `db`, ready to be served or called in-process.
+pub fn app(config: &ServerConfig, db: Arc<DB>) -> Result<Router, Box<dyn std::error::Error>> {
+    let api = track(
+        Router::new()
+            .route("/years", get(years))
+            .route("/:year/counties", get(counties))
+            .route("/:year/:county/schools", get(schools))
+            .route("/:year/:county/fullSchool/:school", get(school))
+            .route("/:year/:county/unplaced", get(unplaced))
+            .route("/:year/:county/participation", get(participation))
+            .route("/:year/:county/appeals", get(appeals)),
+    )
+    .route("/openapi.json", get(openapi))
+    .route("/docs", get(|| async { Redirect::permanent("docs/") }))
+    .route("/docs/*tail", get(docs))
+    .layer(Extension(Arc::new(swagger_config(config))))
+    .layer(axum::middleware::from_fn(crate::compat::sentinels));
+
+    let app = match config.base_path() {
+        "" => api,
+        base_path => Router::new().nest(base_path, api),
+    }
+    .nest(
+        config.v2_base_path(),
+        track(crate::v2::router())
+            .route("/openapi.json", get(openapi_v2))
+            .merge(
+                Router::new()
+                    .route(
+                        "/graphql",
+                        get(crate::graphql::playground).post(crate::graphql::handler),
+                    )
+                    // responses depend on the query body, so no caching headers here
+                    .route_layer(axum::middleware::from_fn(crate::ratelimit::limit))
+                    .route_layer(axum::middleware::from_fn(metrics::track))
+                    .layer(Extension(crate::graphql::schema(
+                        db.clone(),
+                        Arc::new(config.clone()),
+                    ))),
+            )
+            .layer(Extension(crate::v2::Version2)),
+    )
+    .route("/healthz", get(healthz))
+    .route("/readyz", get(readyz))
+    .route("/metrics", get(metrics))
+    .layer(Extension(db.clone()))
+    .fallback(callback.into_service());
+
+    let app = if config.rate_limit.enabled {
+        app.layer(Extension(Arc::new(RateLimiter::new(&config.rate_limit)?)))
+    } else {
+        app
+    };
+
+    Ok(app
+        .layer(Extension(Arc::new(config.clone())))
+        .layer(cors(config)?)
+        // the CORS layer overwrites Vary, so this has to come after it
+        .layer(SetResponseHeaderLayer::appending(
+            VARY,
+            HeaderValue::from_static("accept-encoding"),
+        ))
+        .layer(CompressionLayer::new()))
+}
+
+pub async fn run_server(config: ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let db = Arc::new(DB::new(
+        config.data_dir.clone(),
+        config.pool_size,
+        config.cache.entries,
+    ));
+    let app = app(&config, db.clone())?;
+
+    let addr = config.bind;
+    let drain_timeout = Duration::from_secs(config.shutdown_timeout);
+    let (draining, drain_started) = tokio::sync::oneshot::channel();
+    let server = axum::Server::bind(&addr)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async {
+            shutdown_signal().await;
+            tracing::info!(timeout = ?drain_timeout, "shutting down, draining connections");
+            let _ = draining.send(());
+        });
+
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            if drain_started.await.is_ok() {
+                tokio::time::sleep(drain_timeout).await;
+            } else {
+                std::future::pending::<()>().await;
+            }
+        } => tracing::warn!("drain timeout reached, dropping remaining connections"),
+    }
+
+    db.close_all().await;
+    tracing::info!("server stopped");
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or SIGTERM where there is one.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+pub(crate) fn error_kind(err: &(dyn std::error::Error + 'static)) -> &'static str {
+    if let Some(err) = err.downcast_ref::<repository::Error>() {
+        return match err {
+            repository::Error::UnknownYear(_) => "unknown_year",
+            repository::Error::Busy => "pool_timeout",
+            repository::Error::Database(_) => "database",
+            repository::Error::Io(_) => "io",
+        };
+    }
+    if let Some(err) = err.downcast_ref::<std::io::Error>() {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            // there is no database for the requested year
+            return "unknown_year";
+        }
+        return "io";
+    }
+    match err.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::RowNotFound) => "not_found",
+        Some(sqlx::Error::PoolTimedOut) => "pool_timeout",
+        Some(_) => "database",
+        None => "internal",
+    }
+}
+
+fn success<T>(data: T) -> Json<Status<T>> {
+    Json(Status::success(data))
+}
+
+/// Report a failed query, counting it by what went wrong.
+fn failed<T>(err: impl Into<Box<dyn std::error::Error>>) -> Json<Status<T>> {
+    let err = err.into();
+    metrics::error(error_kind(err.as_ref()));
+    error(err.to_string())
+}
+
+fn bad_request<T>(message: String) -> Json<Status<T>> {
+    metrics::error("bad_request");
+    error(message)
+}
+
+pub(crate) fn error<T>(message: String) -> Json<Status<T>> {
+    Json(Status::error(message))
 }
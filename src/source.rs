@@ -0,0 +1,105 @@
+use crate::fetch::Fetcher;
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use sha2::{Digest, Sha256};
+
+/// A remote file as last seen by the generator.
+#[derive(Debug, sqlx::FromRow)]
+struct Source {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    hash: String,
+}
+
+/// What replacing a county's data from one published file amounted to.
+#[derive(Debug, Clone, Copy)]
+pub struct Refresh {
+    pub rows: usize,
+    pub bytes: usize,
+}
+
+/// A remote file whose contents differ from what the database was built from.
+pub struct Fetched {
+    pub url: String,
+    pub body: Vec<u8>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    hash: String,
+}
+
+impl Fetched {
+    /// Remember this version of the file, so the next run can skip it if unchanged.
+    pub async fn record(&self, db: &mut sqlx::SqliteConnection) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "
+INSERT INTO sources (url, etag, last_modified, hash) VALUES (?, ?, ?, ?)
+ON CONFLICT (url) DO UPDATE SET etag = excluded.etag, last_modified = excluded.last_modified, hash = excluded.hash",
+        )
+        .bind(&self.url)
+        .bind(&self.etag)
+        .bind(&self.last_modified)
+        .bind(&self.hash)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Download `url` unless the server or the content hash says it hasn't changed since it was
+/// last recorded. Returns `None` for unchanged files.
+pub async fn fetch(
+    fetcher: &Fetcher,
+    db: &sqlx::SqlitePool,
+    url: &str,
+    force: bool,
+) -> Result<Option<Fetched>, Box<dyn std::error::Error>> {
+    let known = if force {
+        None
+    } else {
+        sqlx::query_as::<_, Source>("SELECT etag, last_modified, hash FROM sources WHERE url = ?")
+            .bind(url)
+            .fetch_optional(db)
+            .await?
+    };
+
+    let mut headers = HeaderMap::new();
+    if let Some(known) = &known {
+        if let Some(etag) = known.etag.as_ref().and_then(|x| x.parse().ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = known.last_modified.as_ref().and_then(|x| x.parse().ok()) {
+            headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let page = fetcher.get_with(url, headers).await?;
+    if page.status == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !page.status.is_success() {
+        return Err(format!("{url} returned {}", page.status).into());
+    }
+
+    let header = |name| {
+        page.headers
+            .get(name)
+            .and_then(|val: &reqwest::header::HeaderValue| val.to_str().ok())
+            .map(String::from)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+
+    let body = page.body;
+    let hash = format!("{:x}", Sha256::digest(&body));
+    if matches!(known, Some(known) if known.hash == hash) {
+        return Ok(None);
+    }
+
+    Ok(Some(Fetched {
+        url: url.to_string(),
+        body,
+        etag,
+        last_modified,
+        hash,
+    }))
+}
@@ -1,6 +1,11 @@
 use sqlx::Executor;
 
 use crate::county::County;
+use crate::fetch::Fetcher;
+use crate::round::{self, Round};
+use crate::source;
+
+pub use repartizare_api::Specializare;
 
 #[derive(Debug, serde::Deserialize)]
 struct RawSpecializare {
@@ -43,141 +48,195 @@ struct RawSpecializare {
     ultima_medie_anterior: String,
 }
 
-#[derive(Debug, serde::Serialize, sqlx::FromRow)]
-pub struct Specializare {
-    pub id: i32,
-
-    pub name: String,
-    pub judet: String,
-
-    pub liceu: String,
-    pub mediu: String,
-
-    pub specializare: String,
-    pub bilingv: bool,
-
-    pub locuri: i32,
-    pub ocupate: i32,
-
-    pub profil: String,
-    pub filiera: String,
-
-    pub ultima_medie: f64,
-    #[serde(rename = "ultima_medie_ant")]
-    #[sqlx(rename = "ultima_medie_ant")]
-    pub ultima_medie_anterior: f64,
-}
-
-impl Specializare {
-    fn from_raw(st: &RawSpecializare) -> Specializare {
-        let mut name = format!("{}: {}", st.cod, st.specializare);
-        if st.limba_bilingv != "-" {
-            name = format!("{} (Bilingv {})", name, st.limba_bilingv);
-        }
-
-        Specializare {
-            id: st.cod.parse().unwrap(),
-            name: name,
-            judet: st.judet.clone(),
-            liceu: st.liceu.clone(),
-            mediu: st.mediu.clone(),
-            specializare: st.specializare.clone(),
-            bilingv: st.limba_bilingv != "-",
-            locuri: st.nr_locuri_total.parse().unwrap(),
-            ocupate: st.nr_locuri_ocupate.parse().unwrap(),
-            profil: st.profil.clone(),
-            filiera: st.filiera.clone(),
-            ultima_medie: match st.ultima_medie.clone() {
-                Some(um) => um.parse().unwrap_or(-1.0),
-                None => -1.0,
-            },
-            ultima_medie_anterior: st.ultima_medie_anterior.parse().unwrap_or(-1.0),
-        }
+fn from_raw(st: &RawSpecializare, round: Round) -> Specializare {
+    let mut name = format!("{}: {}", st.cod, st.specializare);
+    if st.limba_bilingv != "-" {
+        name = format!("{} (Bilingv {})", name, st.limba_bilingv);
     }
 
-    pub fn nerepartizat(county: &County) -> Specializare {
-        let nerep = format!("Nerepartizat {}", county.code);
-        Specializare {
-            id: -county.id,
-            name: nerep.clone(),
-            judet: county.code.clone(),
-            liceu: "-".to_string(),
-            mediu: "-".to_string(),
-            specializare: nerep.clone(),
-            bilingv: false,
-            locuri: -1,
-            ocupate: -1,
-            profil: nerep.clone(),
-            filiera: "-".to_string(),
-            ultima_medie: -1.0,
-            ultima_medie_anterior: -1.0,
-        }
+    Specializare {
+        id: st.cod.parse().unwrap(),
+        round,
+        name,
+        judet: st.judet.clone(),
+        liceu: st.liceu.clone(),
+        mediu: st.mediu.clone(),
+        specializare: st.specializare.clone(),
+        bilingv: st.limba_bilingv != "-",
+        locuri: Some(st.nr_locuri_total.parse().unwrap()),
+        ocupate: Some(st.nr_locuri_ocupate.parse().unwrap()),
+        profil: st.profil.clone(),
+        filiera: st.filiera.clone(),
+        ultima_medie: st.ultima_medie.as_ref().and_then(|um| um.parse().ok()),
+        ultima_medie_anterior: st.ultima_medie_anterior.parse().ok(),
     }
 }
 
-async fn get_all(year: i32, county: &County) -> Result<Vec<Specializare>, reqwest::Error> {
-    let body = reqwest::get(
-        format!(
-            "http://static.admitere.edu.ro/{year}/repartizare/{}/data/specialization.json",
-            county.code
-        )
-        .as_str(),
+fn url(fetcher: &Fetcher, year: i32, round: Round, county: &County) -> String {
+    round::url(
+        fetcher.admitere_url(),
+        round,
+        year,
+        &format!("{}/data/specialization.json", county.code),
     )
-    .await?
-    .json::<Vec<RawSpecializare>>()
-    .await?;
+}
 
-    Ok(body
+fn parse(body: &[u8], round: Round) -> Result<Vec<Specializare>, serde_json::Error> {
+    Ok(serde_json::from_slice::<Vec<RawSpecializare>>(body)?
         .iter()
-        .map(|x| Specializare::from_raw(x))
+        .map(|x| from_raw(x, round))
         .collect::<Vec<Specializare>>())
 }
 
-pub async fn insert_specializari(
+pub(crate) async fn get_all(
+    fetcher: &Fetcher,
     year: i32,
+    round: Round,
+    county: &County,
+) -> Result<Vec<Specializare>, Box<dyn std::error::Error>> {
+    let body = fetcher.get_ok(&url(fetcher, year, round, county)).await?;
+    Ok(parse(&body, round)?)
+}
+
+/// Download the county's specializari for `round`.
+/// Returns `None` if the published file is unchanged since the last run.
+pub async fn fetch_specializari(
+    fetcher: &Fetcher,
+    year: i32,
+    round: Round,
     county: &County,
     db: &sqlx::Pool<sqlx::Sqlite>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut tx = db.begin().await?;
-    insert_specializare(&Specializare::nerepartizat(county), &mut tx).await?;
-    for sp in get_all(year, county).await? {
-        insert_specializare(&sp, &mut tx).await?;
+    force: bool,
+) -> Result<Option<source::Fetched>, Box<dyn std::error::Error>> {
+    source::fetch(fetcher, db, &url(fetcher, year, round, county), force).await
+}
+
+/// Replace the county's specializari for `round` with the downloaded file, inside the caller's
+/// transaction.
+pub async fn replace_specializari(
+    fetched: &source::Fetched,
+    round: Round,
+    county: &County,
+    db: &mut sqlx::SqliteConnection,
+) -> Result<source::Refresh, Box<dyn std::error::Error>> {
+    let specs = parse(&fetched.body, round)?;
+    let refresh = source::Refresh {
+        rows: specs.len(),
+        bytes: fetched.body.len(),
+    };
+
+    sqlx::query("DELETE FROM specializari WHERE judet = ? AND round = ?")
+        .bind(&county.code)
+        .bind(round)
+        .execute(&mut *db)
+        .await?;
+    for sp in specs {
+        insert_specializare(&sp, db).await?;
     }
+    fetched.record(db).await?;
 
-    tx.commit().await?;
-    Ok(())
+    Ok(refresh)
 }
 
-async fn insert_specializare(
+pub(crate) async fn insert_specializare(
     sp: &Specializare,
     db: &mut sqlx::SqliteConnection,
 ) -> Result<(), sqlx::Error> {
     match db.execute(sqlx::query(
         "
 INSERT INTO specializari
-(id, name, liceu, mediu, judet, specializare, bilingv, locuri, ocupate, profil, filiera, ultima_medie, ultima_medie_ant)
+(id, round, name, liceu, mediu, judet, specializare, bilingv, locuri, ocupate, profil, filiera, ultima_medie, ultima_medie_ant)
 VALUES
-(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
 )
-    .bind(&sp.id)
+    .bind(sp.id)
+    .bind(sp.round)
     .bind(&sp.name)
     .bind(&sp.liceu)
     .bind(&sp.mediu)
     .bind(&sp.judet)
     .bind(&sp.specializare)
-    .bind(&sp.bilingv)
-    .bind(&sp.locuri)
-    .bind(&sp.ocupate)
+    .bind(sp.bilingv)
+    .bind(sp.locuri)
+    .bind(sp.ocupate)
     .bind(&sp.profil)
     .bind(&sp.filiera)
-    .bind(&sp.ultima_medie)
-    .bind(&sp.ultima_medie_anterior)
+    .bind(sp.ultima_medie)
+    .bind(sp.ultima_medie_anterior)
 )
-    .await {
+    .await
+    {
         Ok(_) => Ok(()),
-        Err(_) => {
-            println!("{:#?}", sp);
-            Ok(())
+        Err(err) => {
+            tracing::error!(specializare = ?sp, "failed to insert");
+            Err(err)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALBA: &[u8] =
+        include_bytes!("../tests/fixtures/admitere/2023/repartizare/AB/data/specialization.json");
+
+    fn raw(json: &str) -> RawSpecializare {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn parses_published_file() {
+        let specs = parse(ALBA, Round::First).unwrap();
+
+        assert_eq!(specs.len(), 3);
+        let spec = &specs[0];
+        assert_eq!(spec.id, 101);
+        assert_eq!(spec.round, Round::First);
+        assert_eq!(spec.name, "101: Matematica-Informatica");
+        assert_eq!(spec.judet, "AB");
+        assert_eq!(spec.liceu, "Colegiul Național Horea, Cloșca și Crișan");
+        assert_eq!(spec.locuri, Some(28));
+        assert_eq!(spec.ocupate, Some(2));
+        assert_eq!(spec.ultima_medie, Some(9.12));
+        assert_eq!(spec.ultima_medie_anterior, Some(8.95));
+        assert!(!spec.bilingv);
+    }
+
+    #[test]
+    fn bilingual_language_is_part_of_the_name() {
+        let spec = &parse(ALBA, Round::First).unwrap()[1];
+
+        assert!(spec.bilingv);
+        assert_eq!(spec.name, "102: Filologie (Bilingv Engleza)");
+        assert_eq!(spec.specializare, "Filologie");
+    }
+
+    #[test]
+    fn missing_last_average() {
+        let spec = &parse(ALBA, Round::First).unwrap()[2];
+
+        assert_eq!(spec.ultima_medie, None);
+        assert_eq!(spec.ultima_medie_anterior, None);
+    }
+
+    #[test]
+    fn accepts_long_field_names() {
+        let spec = from_raw(
+            &raw(r#"{
+                    "judet": "CV", "cod": "7", "liceu": "L", "_liceu_id": "1",
+                    "mediu": "Rural", "specializare": "Agricultura", "_limba_predare": "romana",
+                    "limba_bilingv": "-", "nr_locuri_total": "14", "nr_locuri_ocupate": "0",
+                    "_forma_invatamant": "zi", "profil": "Tehnic", "filiera": "Tehnologica",
+                    "_nivel": "liceu", "ultima_medie": "", "ultima_medie_anterior": "5.10"
+                }"#),
+            Round::Second,
+        );
+
+        assert_eq!(spec.id, 7);
+        assert_eq!(spec.round, Round::Second);
+        assert_eq!(spec.mediu, "Rural");
+        assert_eq!(spec.ultima_medie, None);
+        assert_eq!(spec.ultima_medie_anterior, Some(5.1));
+    }
+}
@@ -1,7 +1,12 @@
 use crate::county::County;
+use crate::fetch::Fetcher;
+use crate::round::{self, Round};
+use crate::source;
 use regex::Regex;
 use sqlx::Executor;
 
+pub use repartizare_api::{Placement, Student};
+
 #[derive(Debug, serde::Deserialize)]
 struct RawStudent {
     #[serde(alias = "ja")]
@@ -38,110 +43,204 @@ struct RawStudent {
     specializare: String,
 }
 
-#[derive(Debug, serde::Serialize, sqlx::FromRow)]
-pub struct Student {
-    pub id: String,
-    pub provenienta: String,
-    pub judet: String,
-
-    #[serde(rename = "medie_adm")]
-    #[sqlx(rename = "medie_adm")]
-    pub medie_admitere: f64,
-    #[serde(rename = "medie_en")]
-    #[sqlx(rename = "medie_en")]
-    pub medie_evaluare: f64,
-    #[serde(rename = "medie_abs")]
-    #[sqlx(rename = "medie_abs")]
-    pub medie_absolvire: f64,
-
-    #[serde(rename = "nota_ro")]
-    #[sqlx(rename = "nota_ro")]
-    pub nota_romana: f64,
-    #[serde(rename = "nota_mate")]
-    #[sqlx(rename = "nota_mate")]
-    pub nota_mate: f64,
-
-    pub liceu: String,
-    pub id_specializare: i32,
-    #[serde(rename = "specializare_display")]
-    #[sqlx(rename = "specializare_display")]
-    pub specializare: String,
-}
-
-impl Student {
-    fn from_raw(st: &RawStudent, county_id: i32) -> Student {
-        let finder_regex = Regex::new("([0-9]+)").unwrap();
-        Student {
-            id: st.id.clone(),
-            provenienta: st.scoala_provenienta.clone(),
-            judet: st.judet_id.clone(),
-
-            medie_admitere: st.medie_admitere.parse().unwrap_or(-1.0),
-            medie_evaluare: st.medie_evaluare.parse().unwrap_or(-1.0),
-            medie_absolvire: st.medie_absolvire.parse().unwrap_or(-1.0),
-
-            nota_romana: st.nota_ro.parse().unwrap(),
-            nota_mate: st.nota_mate.parse().unwrap(),
-
-            liceu: st.liceu.clone(),
-            id_specializare: if st.specializare == "Nerepartizat" {
-                -county_id
-            } else {
+fn from_raw(st: &RawStudent, round: Round) -> Student {
+    let finder_regex = Regex::new("([0-9]+)").unwrap();
+    let unplaced = st.specializare == "Nerepartizat";
+    Student {
+        id: st.id.clone(),
+        round,
+        provenienta: st.scoala_provenienta.clone(),
+        judet: st.judet_id.clone(),
+
+        medie_admitere: st.medie_admitere.parse().ok(),
+        medie_evaluare: st.medie_evaluare.parse().ok(),
+        medie_absolvire: st.medie_absolvire.parse().ok(),
+
+        nota_romana: st.nota_ro.parse().ok(),
+        nota_mate: st.nota_mate.parse().ok(),
+
+        liceu: st.liceu.clone(),
+        status: if unplaced {
+            Placement::Unplaced
+        } else {
+            round.placement()
+        },
+        id_specializare: if unplaced {
+            None
+        } else {
+            Some(
                 finder_regex.captures(&st.specializare).unwrap()[0]
                     .parse()
-                    .unwrap()
-            },
-            specializare: st.specializare.clone(),
-        }
+                    .unwrap(),
+            )
+        },
+        specializare: st.specializare.clone(),
     }
 }
 
-async fn get_all(year: i32, county: &County) -> Result<Vec<Student>, reqwest::Error> {
-    let body = reqwest::get(
-        format!(
-            "http://static.admitere.edu.ro/{year}/repartizare/{}/data/candidate.json",
-            county.code
-        )
-        .as_str(),
+fn url(fetcher: &Fetcher, year: i32, round: Round, county: &County) -> String {
+    round::url(
+        fetcher.admitere_url(),
+        round,
+        year,
+        &format!("{}/data/candidate.json", county.code),
     )
-    .await?
-    .json::<Vec<RawStudent>>()
-    .await?;
+}
 
-    Ok(body
+fn parse(body: &[u8], round: Round) -> Result<Vec<Student>, serde_json::Error> {
+    Ok(serde_json::from_slice::<Vec<RawStudent>>(body)?
         .iter()
-        .map(|x| Student::from_raw(x, county.id))
+        .map(|x| from_raw(x, round))
         .collect::<Vec<Student>>())
 }
 
-pub async fn insert_students(
+pub(crate) async fn get_all(
+    fetcher: &Fetcher,
     year: i32,
+    round: Round,
+    county: &County,
+) -> Result<Vec<Student>, Box<dyn std::error::Error>> {
+    let body = fetcher.get_ok(&url(fetcher, year, round, county)).await?;
+    Ok(parse(&body, round)?)
+}
+
+/// Download the county's students for `round`.
+/// Returns `None` if the published file is unchanged since the last run.
+pub async fn fetch_students(
+    fetcher: &Fetcher,
+    year: i32,
+    round: Round,
     county: &County,
     db: &sqlx::Pool<sqlx::Sqlite>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut tx = db.begin().await?;
-    for st in get_all(year, &county).await? {
-        tx.execute(sqlx::query("
+    force: bool,
+) -> Result<Option<source::Fetched>, Box<dyn std::error::Error>> {
+    source::fetch(fetcher, db, &url(fetcher, year, round, county), force).await
+}
+
+/// Replace the county's students for `round` with the downloaded file, inside the caller's
+/// transaction.
+pub async fn replace_students(
+    fetched: &source::Fetched,
+    round: Round,
+    county: &County,
+    db: &mut sqlx::SqliteConnection,
+) -> Result<source::Refresh, Box<dyn std::error::Error>> {
+    let students = parse(&fetched.body, round)?;
+    let refresh = source::Refresh {
+        rows: students.len(),
+        bytes: fetched.body.len(),
+    };
+
+    sqlx::query("DELETE FROM students WHERE judet = ? AND round = ?")
+        .bind(&county.code)
+        .bind(round)
+        .execute(&mut *db)
+        .await?;
+    for st in students {
+        insert_student(&st, db).await?;
+    }
+    fetched.record(db).await?;
+
+    Ok(refresh)
+}
+
+pub(crate) async fn insert_student(
+    st: &Student,
+    db: &mut sqlx::SqliteConnection,
+) -> Result<(), sqlx::Error> {
+    db.execute(sqlx::query("
 INSERT INTO students 
-    (id, provenienta, medie_adm, medie_en, medie_abs, nota_ro, nota_mate, liceu, id_specializare, specializare_display, judet) 
+    (id, round, provenienta, medie_adm, medie_en, medie_abs, nota_ro, nota_mate, liceu, status, id_specializare, specializare_display, judet) 
 VALUES 
-    (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
 )
         .bind(&st.id)
+        .bind(st.round)
         .bind(&st.provenienta)
-        .bind(&st.medie_admitere)
-        .bind(&st.medie_evaluare)
-        .bind(&st.medie_absolvire)
-        .bind(&st.nota_romana)
-        .bind(&st.nota_mate)
+        .bind(st.medie_admitere)
+        .bind(st.medie_evaluare)
+        .bind(st.medie_absolvire)
+        .bind(st.nota_romana)
+        .bind(st.nota_mate)
         .bind(&st.liceu)
-        .bind(&st.id_specializare)
+        .bind(st.status)
+        .bind(st.id_specializare)
         .bind(&st.specializare)
         .bind(&st.judet)
     )
-        .await?;
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALBA: &[u8] =
+        include_bytes!("../tests/fixtures/admitere/2023/repartizare/AB/data/candidate.json");
+
+    #[test]
+    fn parses_published_file() {
+        let students = parse(ALBA, Round::First).unwrap();
+
+        assert_eq!(students.len(), 6);
+        let student = &students[0];
+        assert_eq!(student.id, "AB1");
+        assert_eq!(student.judet, "AB");
+        assert_eq!(student.provenienta, "Școala Gimnazială Nr. 1 Alba Iulia");
+        assert_eq!(student.medie_admitere, Some(9.5));
+        assert_eq!(student.medie_evaluare, Some(9.4));
+        assert_eq!(student.medie_absolvire, Some(9.9));
+        assert_eq!(student.nota_romana, Some(9.5));
+        assert_eq!(student.nota_mate, Some(9.3));
+        assert_eq!(student.status, Placement::Placed);
+        assert_eq!(student.id_specializare, Some(101));
     }
 
-    tx.commit().await?;
-    Ok(())
+    #[test]
+    fn specialization_id_comes_from_its_label() {
+        let student = &parse(ALBA, Round::First).unwrap()[2];
+
+        assert_eq!(student.specializare, "(102) Filologie (Bilingv Engleza)");
+        assert_eq!(student.id_specializare, Some(102));
+    }
+
+    #[test]
+    fn nerepartizat_is_unplaced() {
+        let student = &parse(ALBA, Round::First).unwrap()[4];
+
+        assert_eq!(student.status, Placement::Unplaced);
+        assert_eq!(student.id_specializare, None);
+        assert_eq!(student.medie_admitere, Some(5.2));
+    }
+
+    #[test]
+    fn missing_averages() {
+        let student = &parse(ALBA, Round::First).unwrap()[5];
+
+        assert_eq!(student.status, Placement::Unplaced);
+        assert_eq!(student.medie_admitere, None);
+        assert_eq!(student.medie_absolvire, None);
+        assert_eq!(student.medie_evaluare, Some(3.95));
+    }
+
+    #[test]
+    fn missing_grades() {
+        let mut raw: Vec<RawStudent> = serde_json::from_slice(ALBA).unwrap();
+        raw[5].nota_ro = "-".to_string();
+        raw[5].nota_mate = String::new();
+        let student = from_raw(&raw[5], Round::First);
+
+        assert_eq!(student.nota_romana, None);
+        assert_eq!(student.nota_mate, None);
+    }
+
+    #[test]
+    fn later_rounds_redistribute() {
+        let students = parse(ALBA, Round::Second).unwrap();
+
+        assert_eq!(students[0].round, Round::Second);
+        assert_eq!(students[0].status, Round::Second.placement());
+        assert_eq!(students[4].status, Placement::Unplaced);
+    }
 }
@@ -0,0 +1,539 @@
+use crate::config::ServerConfig;
+use crate::contestatie::Contestatie;
+use crate::county::County;
+use crate::dbmgr::{FullSchool, GradeBucket, Participation, Unplaced, DB};
+use crate::repository;
+use crate::round::Round;
+use crate::specializare::Specializare;
+use crate::student::{Placement, Student};
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+#[aliases(
+    YearsData = Data<Vec<i32>>,
+    CountiesData = Data<Vec<CountyV2>>,
+    SchoolsData = Data<Vec<SchoolSummary>>,
+    SchoolData = Data<School>,
+    UnplacedData = Data<UnplacedV2>,
+    ParticipationData = Data<ParticipationV2>,
+    AppealsData = Data<Vec<Appeal>>
+)]
+pub(crate) struct Data<T> {
+    data: T,
+}
+
+fn data<T>(data: T) -> Json<Data<T>> {
+    Json(Data { data })
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorBody {
+    /// Stable identifier of the error, e.g. `unknown_year` or `bad_request`
+    #[schema(value_type = String)]
+    code: &'static str,
+    message: String,
+}
+
+/// Body of every response with a 4xx or 5xx status.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ErrorResponse {
+    error: ErrorBody,
+}
+
+pub(crate) struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: String) -> ApiError {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            code: "bad_request",
+            message,
+        }
+    }
+
+    /// The body of the error, without counting it in the metrics.
+    pub(crate) fn body(self) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            self.status,
+            Json(ErrorResponse {
+                error: ErrorBody {
+                    code: self.code,
+                    message: self.message,
+                },
+            }),
+        )
+    }
+}
+
+/// Marks requests to the second version, whose errors are written as [`ErrorResponse`] even when
+/// they come from middleware shared with the original API.
+#[derive(Clone, Copy)]
+pub(crate) struct Version2;
+
+pub(crate) fn too_many_requests() -> ApiError {
+    ApiError {
+        status: StatusCode::TOO_MANY_REQUESTS,
+        code: "rate_limited",
+        message: "Too many requests, try again later".to_string(),
+    }
+}
+
+/// Answers paths under the second version that match no route.
+pub(crate) fn not_found() -> ApiError {
+    ApiError {
+        status: StatusCode::NOT_FOUND,
+        code: "not_found",
+        message: "No such route".to_string(),
+    }
+}
+
+impl From<repository::Error> for ApiError {
+    fn from(err: repository::Error) -> ApiError {
+        let code = crate::server::error_kind(&err);
+        let status = match code {
+            "unknown_year" | "not_found" => StatusCode::NOT_FOUND,
+            "pool_timeout" => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let message = match code {
+            "unknown_year" => "No data for this year".to_string(),
+            _ => err.to_string(),
+        };
+        ApiError {
+            status,
+            code,
+            message,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        crate::metrics::error(self.code);
+        self.body().into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<Data<T>>, ApiError>;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct RoundQuery {
+    /// 1 for the first repartizare (default), 2 for the second, 3 for the special seats
+    #[param(value_type = Option<i32>)]
+    round: Option<String>,
+}
+
+impl RoundQuery {
+    fn round(&self) -> Result<Round, ApiError> {
+        self.round
+            .as_deref()
+            .map_or(Ok(Round::default()), str::parse)
+            .map_err(ApiError::bad_request)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct CountyV2 {
+    id: Option<i32>,
+    code: String,
+    name: String,
+}
+
+impl From<&County> for CountyV2 {
+    fn from(county: &County) -> CountyV2 {
+        CountyV2 {
+            id: county.id,
+            code: county.code.clone(),
+            name: county.name.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SchoolSummary {
+    name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Candidate {
+    id: String,
+    county: String,
+    round: Round,
+    status: Placement,
+    origin_school: String,
+    admission_average: Option<f64>,
+    national_exam_average: Option<f64>,
+    graduation_average: Option<f64>,
+    romanian_grade: Option<f64>,
+    math_grade: Option<f64>,
+    school: String,
+    program_id: Option<i32>,
+    program: String,
+}
+
+impl From<&Student> for Candidate {
+    fn from(student: &Student) -> Candidate {
+        Candidate {
+            id: student.id.clone(),
+            county: student.judet.clone(),
+            round: student.round,
+            status: student.status,
+            origin_school: student.provenienta.clone(),
+            admission_average: student.medie_admitere,
+            national_exam_average: student.medie_evaluare,
+            graduation_average: student.medie_absolvire,
+            romanian_grade: student.nota_romana,
+            math_grade: student.nota_mate,
+            school: student.liceu.clone(),
+            program_id: student.id_specializare,
+            program: student.specializare.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Program {
+    id: i32,
+    name: String,
+    specialization: String,
+    profile: String,
+    track: String,
+    environment: String,
+    bilingual: bool,
+    seats: Option<i32>,
+    filled: Option<i32>,
+    last_admission_average: Option<f64>,
+    previous_last_admission_average: Option<f64>,
+    candidates: Vec<Candidate>,
+}
+
+impl Program {
+    fn new(spec: &Specializare, students: &[Student]) -> Program {
+        Program {
+            id: spec.id,
+            name: spec.name.clone(),
+            specialization: spec.specializare.clone(),
+            profile: spec.profil.clone(),
+            track: spec.filiera.clone(),
+            environment: spec.mediu.clone(),
+            bilingual: spec.bilingv,
+            seats: spec.locuri,
+            filled: spec.ocupate,
+            last_admission_average: spec.ultima_medie,
+            previous_last_admission_average: spec.ultima_medie_anterior,
+            candidates: students.iter().map(Candidate::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct School {
+    name: String,
+    county: String,
+    round: Round,
+    programs: Vec<Program>,
+}
+
+impl School {
+    fn new(name: String, county: String, round: Round, school: &FullSchool) -> School {
+        School {
+            name,
+            county,
+            round,
+            programs: school
+                .specializari_short
+                .iter()
+                .filter_map(|short| school.specializari.get(&short.id))
+                .map(|full| Program::new(&full.spec, &full.elevi))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct AverageBucket {
+    /// Averages in `[grade, grade + 1)`.
+    grade: i32,
+    count: i64,
+}
+
+impl From<&GradeBucket> for AverageBucket {
+    fn from(bucket: &GradeBucket) -> AverageBucket {
+        AverageBucket {
+            grade: bucket.grade,
+            count: bucket.count,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct UnplacedV2 {
+    candidates: Vec<Candidate>,
+    distribution: Vec<AverageBucket>,
+}
+
+impl From<&Unplaced> for UnplacedV2 {
+    fn from(unplaced: &Unplaced) -> UnplacedV2 {
+        UnplacedV2 {
+            candidates: unplaced.elevi.iter().map(Candidate::from).collect(),
+            distribution: unplaced
+                .distribution
+                .iter()
+                .map(AverageBucket::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ParticipationV2 {
+    registered: i64,
+    present: i64,
+    absent: i64,
+    appealed: i64,
+    admitted: i64,
+}
+
+impl From<&Participation> for ParticipationV2 {
+    fn from(participation: &Participation) -> ParticipationV2 {
+        ParticipationV2 {
+            registered: participation.inscrisi,
+            present: participation.prezenti,
+            absent: participation.absenti,
+            appealed: participation.contestatii,
+            admitted: participation.admitere,
+        }
+    }
+}
+
+/// A value before and after appeal.
+#[derive(Serialize, ToSchema)]
+#[aliases(GradeChange = Change<f64>, RankChange = Change<usize>)]
+pub(crate) struct Change<T> {
+    initial: Option<T>,
+    after_appeal: Option<T>,
+}
+
+fn change<T>(initial: Option<T>, after_appeal: Option<T>) -> Change<T> {
+    Change {
+        initial,
+        after_appeal,
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct AppealGrades {
+    #[schema(value_type = GradeChange)]
+    romanian: Change<f64>,
+    #[schema(value_type = GradeChange)]
+    math: Change<f64>,
+    #[schema(value_type = GradeChange)]
+    native_language: Change<f64>,
+    #[schema(value_type = GradeChange)]
+    national_exam_average: Change<f64>,
+    #[schema(value_type = GradeChange)]
+    admission_average: Change<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Appeal {
+    candidate_id: String,
+    county: String,
+    grades: AppealGrades,
+    /// Position in the county by admission average.
+    #[schema(value_type = RankChange)]
+    rank: Change<usize>,
+}
+
+impl From<&Contestatie> for Appeal {
+    fn from(appeal: &Contestatie) -> Appeal {
+        Appeal {
+            candidate_id: appeal.id.clone(),
+            county: appeal.judet.clone(),
+            grades: AppealGrades {
+                romanian: change(appeal.nota_ro_initiala, appeal.nota_ro),
+                math: change(appeal.nota_mate_initiala, appeal.nota_mate),
+                native_language: change(appeal.nota_lm_initiala, appeal.nota_lm),
+                national_exam_average: change(appeal.medie_en_initiala, appeal.medie_en),
+                admission_average: change(appeal.medie_admitere_initiala, appeal.medie_admitere),
+            },
+            rank: change(appeal.loc_initial, appeal.loc),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/years",
+    responses(
+        (status = 200, description = "Years with published results", body = YearsData),
+    )
+)]
+pub(crate) async fn years(Extension(config): Extension<Arc<ServerConfig>>) -> Json<Data<Vec<i32>>> {
+    data(config.years.clone())
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/counties",
+    params(("year" = i32, Path, description = "Year of the admission")),
+    responses(
+        (status = 200, description = "Counties", body = CountiesData),
+        (status = 404, description = "No data for this year", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn counties(
+    Extension(db): Extension<Arc<DB>>,
+    Path(year): Path<i32>,
+) -> ApiResult<Vec<CountyV2>> {
+    let counties = db.get_counties(year).await?;
+    Ok(data(counties.iter().map(CountyV2::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/counties/{county}/schools",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`"),
+        RoundQuery),
+    responses(
+        (status = 200, description = "High schools with seats in the round", body = SchoolsData),
+        (status = 400, description = "Unknown round", body = ErrorResponse),
+        (status = 404, description = "No data for this year", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn schools(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+    Query(query): Query<RoundQuery>,
+) -> ApiResult<Vec<SchoolSummary>> {
+    let round = query.round()?;
+    let schools = db.get_schools(year, round, county.as_str()).await?;
+    Ok(data(
+        schools
+            .iter()
+            .map(|name| SchoolSummary { name: name.clone() })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/counties/{county}/schools/{school}",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`"),
+        ("school" = String, Path, description = "Name of the high school"),
+        RoundQuery),
+    responses(
+        (status = 200, description = "Every program of the school, with the candidates placed in it", body = SchoolData),
+        (status = 400, description = "Unknown round", body = ErrorResponse),
+        (status = 404, description = "No data for this year, or no school with this name", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn school(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county, school)): Path<(i32, String, String)>,
+    Query(query): Query<RoundQuery>,
+) -> ApiResult<School> {
+    let round = query.round()?;
+    let full = db
+        .get_full_school(year, round, county.as_str(), school.as_str())
+        .await?;
+    if full.specializari_short.is_empty() {
+        return Err(ApiError {
+            status: StatusCode::NOT_FOUND,
+            code: "not_found",
+            message: format!("No school named {school} in {county}"),
+        });
+    }
+    Ok(data(School::new(school, county, round, &full)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/counties/{county}/unplaced",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`"),
+        RoundQuery),
+    responses(
+        (status = 200, description = "Candidates left without a seat, and how their averages are distributed", body = UnplacedData),
+        (status = 400, description = "Unknown round", body = ErrorResponse),
+        (status = 404, description = "No data for this year", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn unplaced(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+    Query(query): Query<RoundQuery>,
+) -> ApiResult<UnplacedV2> {
+    let round = query.round()?;
+    let unplaced = db.get_unplaced(year, round, county.as_str()).await?;
+    Ok(data(UnplacedV2::from(&unplaced)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/counties/{county}/participation",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`")),
+    responses(
+        (status = 200, description = "Evaluare Națională attendance and appeals", body = ParticipationData),
+        (status = 404, description = "No data for this year", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn participation(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+) -> ApiResult<ParticipationV2> {
+    let participation = db.get_participation(year, county.as_str()).await?;
+    Ok(data(ParticipationV2::from(&participation)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{year}/counties/{county}/appeals",
+    params(("year" = i32, Path, description = "Year of the admission"),
+        ("county" = String, Path, description = "County code, e.g. `B` or `CJ`")),
+    responses(
+        (status = 200, description = "Candidates whose grades changed after appeal", body = AppealsData),
+        (status = 404, description = "No data for this year", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+pub(crate) async fn appeals(
+    Extension(db): Extension<Arc<DB>>,
+    Path((year, county)): Path<(i32, String)>,
+) -> ApiResult<Vec<Appeal>> {
+    let appeals = db.get_appeals(year, county.as_str()).await?;
+    Ok(data(appeals.iter().map(Appeal::from).collect()))
+}
+
+/// Second version of the API: English field names, arrays instead of maps keyed by id, `null`
+/// for every missing value and HTTP status codes for errors. Built from the same queries as the
+/// original routes, which stay as they are for existing clients.
+pub fn router() -> Router {
+    Router::new()
+        .route("/years", get(years))
+        .route("/:year/counties", get(counties))
+        .route("/:year/counties/:county/schools", get(schools))
+        .route("/:year/counties/:county/schools/:school", get(school))
+        .route("/:year/counties/:county/unplaced", get(unplaced))
+        .route("/:year/counties/:county/participation", get(participation))
+        .route("/:year/counties/:county/appeals", get(appeals))
+}
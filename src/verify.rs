@@ -0,0 +1,269 @@
+use crate::fetch::{FetchOptions, Fetcher};
+use crate::round::Round;
+use crate::*;
+
+#[derive(sqlx::FromRow)]
+struct KeyCount {
+    judet: String,
+    id: String,
+    count: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct CountyCount {
+    judet: String,
+    count: i64,
+}
+
+async fn check_orphans(
+    db: &sqlx::SqlitePool,
+    problems: &mut Vec<String>,
+) -> Result<(), sqlx::Error> {
+    let orphans = sqlx::query_as::<_, KeyCount>(
+        "
+SELECT s.judet AS judet, CAST(s.id_specializare AS TEXT) AS id, COUNT(*) AS count
+FROM students s
+LEFT JOIN specializari sp ON sp.judet = s.judet AND sp.round = s.round AND sp.id = s.id_specializare
+WHERE s.id_specializare IS NOT NULL AND sp.id IS NULL
+GROUP BY s.judet, s.id_specializare",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for orphan in orphans {
+        problems.push(format!(
+            "{}: {} students placed in unknown specialization {}",
+            orphan.judet, orphan.count, orphan.id
+        ));
+    }
+
+    let unlinked = sqlx::query_as::<_, CountyCount>(
+        "
+SELECT judet, COUNT(*) AS count FROM students
+WHERE (status = 'unplaced') = (id_specializare IS NOT NULL)
+GROUP BY judet",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for county in unlinked {
+        problems.push(format!(
+            "{}: {} students have a placement status that disagrees with their specialization",
+            county.judet, county.count
+        ));
+    }
+
+    let unidentified = sqlx::query_as::<_, CountyCount>(
+        "SELECT code AS judet, 1 AS count FROM counties WHERE id IS NULL",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for county in unidentified {
+        problems.push(format!("{}: county has no official id", county.judet));
+    }
+
+    let unknown_counties = sqlx::query_as::<_, CountyCount>(
+        "
+SELECT judet, COUNT(*) AS count FROM (
+    SELECT judet FROM students UNION ALL SELECT judet FROM specializari
+)
+WHERE judet NOT IN (SELECT code FROM counties)
+GROUP BY judet",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for county in unknown_counties {
+        problems.push(format!(
+            "{}: {} rows reference a county missing from the counties table",
+            county.judet, county.count
+        ));
+    }
+
+    Ok(())
+}
+
+async fn check_duplicates(
+    db: &sqlx::SqlitePool,
+    problems: &mut Vec<String>,
+) -> Result<(), sqlx::Error> {
+    let students = sqlx::query_as::<_, KeyCount>(
+        "SELECT judet, id, COUNT(*) AS count FROM students GROUP BY judet, round, id HAVING COUNT(*) > 1",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for st in students {
+        problems.push(format!(
+            "{}: candidate {} appears {} times",
+            st.judet, st.id, st.count
+        ));
+    }
+
+    let specs = sqlx::query_as::<_, KeyCount>(
+        "
+SELECT judet, CAST(id AS TEXT) AS id, COUNT(*) AS count
+FROM specializari GROUP BY judet, round, id HAVING COUNT(*) > 1",
+    )
+    .fetch_all(db)
+    .await?;
+
+    for sp in specs {
+        problems.push(format!(
+            "{}: specialization {} appears {} times",
+            sp.judet, sp.id, sp.count
+        ));
+    }
+
+    Ok(())
+}
+
+async fn check_sentinels(
+    db: &sqlx::SqlitePool,
+    problems: &mut Vec<String>,
+) -> Result<(), sqlx::Error> {
+    // missing values are NULL since the sentinels were dropped, so only a negative value is wrong
+    let columns = [
+        ("students", "medie_adm"),
+        ("students", "medie_en"),
+        ("students", "medie_abs"),
+        ("students", "nota_ro"),
+        ("students", "nota_mate"),
+        ("specializari", "locuri"),
+        ("specializari", "ocupate"),
+        ("specializari", "ultima_medie"),
+        ("specializari", "ultima_medie_ant"),
+    ];
+
+    for (table, column) in columns {
+        let counts = sqlx::query_as::<_, CountyCount>(&format!(
+            "
+SELECT judet, COUNT(*) AS count FROM {table}
+WHERE {column} < 0
+GROUP BY judet"
+        ))
+        .fetch_all(db)
+        .await?;
+
+        for county in counts {
+            problems.push(format!(
+                "{}: {} rows in {table} have a -1 sentinel in {column}",
+                county.judet, county.count
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+async fn count_rows(
+    db: &sqlx::SqlitePool,
+    query: &str,
+) -> Result<std::collections::HashMap<String, i64>, sqlx::Error> {
+    Ok(sqlx::query_as::<_, CountyCount>(query)
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.judet, x.count))
+        .collect())
+}
+
+async fn check_index(
+    fetcher: &Fetcher,
+    year: i32,
+    db: &sqlx::SqlitePool,
+    problems: &mut Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let stored = sqlx::query_as::<_, county::County>("SELECT * FROM counties")
+        .fetch_all(db)
+        .await?;
+    let specs = count_rows(
+        db,
+        "SELECT judet, COUNT(*) AS count FROM specializari WHERE round = 1 GROUP BY judet",
+    )
+    .await?;
+    let students = count_rows(
+        db,
+        "SELECT judet, COUNT(*) AS count FROM students WHERE round = 1 GROUP BY judet",
+    )
+    .await?;
+
+    let counties = county::get_all(fetcher, year, Round::First).await?;
+    for stored in &stored {
+        if !counties.iter().any(|x| x.code == stored.code) {
+            problems.push(format!(
+                "{}: county is not in the ministry index",
+                stored.code
+            ));
+        }
+    }
+
+    for county in counties {
+        if !stored.iter().any(|x| x.code == county.code) {
+            problems.push(format!(
+                "{}: county is missing from the database",
+                county.code
+            ));
+            continue;
+        }
+
+        let remote_specs = specializare::get_all(fetcher, year, Round::First, &county)
+            .await?
+            .len() as i64;
+        let local_specs = specs.get(&county.code).copied().unwrap_or(0);
+        if remote_specs != local_specs {
+            problems.push(format!(
+                "{}: {local_specs} specializations stored, {remote_specs} published",
+                county.code
+            ));
+        }
+
+        let remote_students = student::get_all(fetcher, year, Round::First, &county)
+            .await?
+            .len() as i64;
+        let local_students = students.get(&county.code).copied().unwrap_or(0);
+        if remote_students != local_students {
+            problems.push(format!(
+                "{}: {local_students} students stored, {remote_students} published",
+                county.code
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn verify_year(
+    data_dir: &std::path::Path,
+    year: i32,
+    offline: bool,
+    opts: &FetchOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = db::create_pool(
+        db::dsn(&db::year_path(data_dir, year))?.as_str(),
+        false,
+        db::DEFAULT_POOL_SIZE,
+    )
+    .await?;
+
+    let mut problems = Vec::new();
+    check_orphans(&db, &mut problems).await?;
+    check_duplicates(&db, &mut problems).await?;
+    check_sentinels(&db, &mut problems).await?;
+    if !offline {
+        check_index(&Fetcher::new(opts)?, year, &db, &mut problems).await?;
+    }
+
+    db.close().await;
+
+    if problems.is_empty() {
+        println!("Year {year} is consistent");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    Err(format!("Found {} problems in year {year}", problems.len()).into())
+}
@@ -1,44 +1,504 @@
+use crate::county::County;
+use crate::fetch::{FetchOptions, Fetcher};
+use crate::round::Round;
+use crate::source::Refresh;
 use crate::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
 
-pub async fn do_year(year: i32) -> Result<(), Box<dyn std::error::Error>> {
-    let db = db::create_pool(format!("sqlite://{year}.db").as_str(), true).await?;
+/// What refreshing one part of a county (a round, or its Evaluare Națională results) did.
+/// Counts are `None` when the published file was unchanged.
+#[derive(Default)]
+struct Report {
+    county: String,
+    part: String,
+    specializari: Option<usize>,
+    students: Option<usize>,
+    evaluare: Option<usize>,
+    bytes: usize,
+    duration: Duration,
+    error: Option<String>,
+}
+
+impl Report {
+    fn new(county: &County, part: String) -> Report {
+        Report {
+            county: county.code.clone(),
+            part,
+            ..Default::default()
+        }
+    }
+
+    fn count(&mut self, refresh: Option<Refresh>) -> Option<usize> {
+        let refresh = refresh?;
+        self.bytes += refresh.bytes;
+        Some(refresh.rows)
+    }
+
+    fn changed(&self) -> bool {
+        self.specializari
+            .or(self.students)
+            .or(self.evaluare)
+            .is_some()
+    }
+
+    fn log(&self) {
+        tracing::info!(
+            county = self.county.as_str(),
+            part = self.part.as_str(),
+            specializari = self.specializari,
+            students = self.students,
+            evaluare = self.evaluare,
+            bytes = self.bytes,
+            duration_ms = self.duration.as_millis() as u64,
+            error = self.error.as_deref(),
+            "summary"
+        );
+    }
+}
+
+/// Files of one county part that changed since the last run.
+enum Changed {
+    Round {
+        round: Round,
+        specs: Option<source::Fetched>,
+        students: Option<source::Fetched>,
+    },
+    Evaluare(Option<source::Fetched>),
+}
+
+/// Download every changed file of the county, then replace them all in one transaction, so readers
+/// of a live database never see one part of the county updated without the others.
+/// `parts` are rounds, or `None` for the Evaluare Națională results, each with its own report.
+async fn replace_county(
+    fetcher: &Fetcher,
+    db: &sqlx::SqlitePool,
+    year: i32,
+    county: &County,
+    parts: &[Option<Round>],
+    force: bool,
+    reports: &mut [Report],
+) -> Result<(), String> {
+    let mut changed = Vec::new();
+    for part in parts {
+        changed.push(match *part {
+            Some(round) => Changed::Round {
+                round,
+                specs: specializare::fetch_specializari(fetcher, year, round, county, db, force)
+                    .await
+                    .map_err(|err| err.to_string())?,
+                students: student::fetch_students(fetcher, year, round, county, db, force)
+                    .await
+                    .map_err(|err| err.to_string())?,
+            },
+            None => Changed::Evaluare(
+                evaluare::fetch_evaluare(fetcher, year, county, db, force)
+                    .await
+                    .map_err(|err| err.to_string())?,
+            ),
+        });
+    }
+
+    let mut tx = db.begin().await.map_err(|err| err.to_string())?;
+    for (part, report) in changed.iter().zip(reports.iter_mut()) {
+        match part {
+            Changed::Round {
+                round,
+                specs,
+                students,
+            } => {
+                // insert specializari
+                if let Some(specs) = specs {
+                    let refresh =
+                        specializare::replace_specializari(specs, *round, county, &mut tx)
+                            .await
+                            .map_err(|err| err.to_string())?;
+                    report.specializari = report.count(Some(refresh));
+                }
+                // insert students
+                if let Some(students) = students {
+                    let refresh = student::replace_students(students, *round, county, &mut tx)
+                        .await
+                        .map_err(|err| err.to_string())?;
+                    report.students = report.count(Some(refresh));
+                }
+            }
+            // insert evaluare nationala results
+            Changed::Evaluare(Some(results)) => {
+                let refresh = evaluare::replace_evaluare(results, county, &mut tx)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                report.evaluare = report.count(Some(refresh));
+            }
+            Changed::Evaluare(None) => {}
+        }
+    }
+    tx.commit().await.map_err(|err| err.to_string())?;
+
+    Ok(())
+}
 
+/// Refresh one county, reporting on each of its parts.
+async fn refresh_county(
+    fetcher: &Fetcher,
+    db: &sqlx::SqlitePool,
+    year: i32,
+    county: &County,
+    parts: &[Option<Round>],
+    force: bool,
+) -> Vec<Report> {
+    let started = Instant::now();
+    let mut reports: Vec<Report> = parts
+        .iter()
+        .map(|part| {
+            let name = part.map_or("evaluare".to_string(), |round| format!("round {round}"));
+            Report::new(county, name)
+        })
+        .collect();
+
+    let result = replace_county(fetcher, db, year, county, parts, force, &mut reports).await;
+    if let Err(err) = &result {
+        tracing::error!(error = err.as_str(), "refresh failed");
+    }
+    for report in &mut reports {
+        report.duration = started.elapsed();
+        if let Err(err) = &result {
+            // the transaction was rolled back, so no part of the county changed
+            report.specializari = None;
+            report.students = None;
+            report.evaluare = None;
+            report.bytes = 0;
+            report.error = Some(err.clone());
+        }
+        report.log();
+    }
+    reports
+}
+
+fn cell(count: Option<usize>) -> String {
+    count.map_or("-".to_string(), |x| x.to_string())
+}
+
+fn print_summary(reports: &[Report]) {
+    println!(
+        "{:<8} {:<10} {:>12} {:>10} {:>10} {:>12} {:>9}  Status",
+        "County", "Part", "Specializari", "Students", "Evaluare", "Bytes", "Time"
+    );
+    for report in reports {
+        println!(
+            "{:<8} {:<10} {:>12} {:>10} {:>10} {:>12} {:>8.1}s  {}",
+            report.county,
+            report.part,
+            cell(report.specializari),
+            cell(report.students),
+            cell(report.evaluare),
+            report.bytes,
+            report.duration.as_secs_f64(),
+            report.error.as_deref().unwrap_or("ok")
+        );
+    }
+}
+
+/// Re-fetch every county whose published files changed since the last run, each county part
+/// replaced in its own transaction.
+async fn refresh(
+    fetcher: &Arc<Fetcher>,
+    db: &sqlx::SqlitePool,
+    year: i32,
+    county: Option<&str>,
+    force: bool,
+) -> Result<Vec<Report>, Box<dyn std::error::Error>> {
     // insert counties
-    let counties = county::get_all(year).await?;
-    futures::future::join_all(counties.iter().map(|county| async {
-        sqlx::query("INSERT INTO counties (code, name) VALUES (?, ?) ON CONFLICT DO NOTHING")
-            .bind(county.code.clone())
-            .bind(county.name.clone())
-            .fetch_all(&db)
-            .await
-    }))
-    .await;
+    let counties = county::get_all(fetcher, year, Round::First).await?;
+    for result in futures::future::join_all(
+        counties
+            .iter()
+            .map(|county| county::insert_county(county, db)),
+    )
+    .await
+    {
+        result?;
+    }
+
+    let counties = match county {
+        Some(code) => vec![counties
+            .into_iter()
+            .find(|x| x.code.eq_ignore_ascii_case(code))
+            .ok_or(format!("County {code} is not in the ministry index"))?],
+        None => counties,
+    };
+
+    // later rounds only show up once the ministry publishes them
+    let mut rounds = Vec::new();
+    for round in Round::ALL {
+        if county::is_published(fetcher, year, round).await? {
+            rounds.push(round);
+        }
+    }
+
+    let with_evaluare = evaluare::is_published(fetcher, year).await?;
+    tracing::info!(
+        year,
+        counties = counties.len(),
+        rounds = ?rounds,
+        evaluare = with_evaluare,
+        "refreshing"
+    );
+
+    let mut parts: Vec<Option<Round>> = rounds.iter().copied().map(Some).collect();
+    if with_evaluare {
+        parts.push(None);
+    }
 
     let mut handles = Vec::new();
     for county in counties {
-        let county1 = county.clone();
         let db1 = db.clone();
-        handles.push(tokio::spawn(async move {
-            // insert specializari
-            specializare::insert_specializari(year, &county1, &db1)
-                .await
-                .unwrap();
-            // insert students
-            student::insert_students(year, &county1, &db1)
-                .await
-                .unwrap();
-            println!("Finished {} for year {}", county1.name, year);
-        }));
+        let fetcher1 = fetcher.clone();
+        let parts1 = parts.clone();
+        let span = tracing::info_span!("county", county = county.code.as_str());
+
+        handles.push(tokio::spawn(
+            async move { refresh_county(&fetcher1, &db1, year, &county, &parts1, force).await }
+                .instrument(span),
+        ));
+    }
+
+    let mut reports = Vec::new();
+    for result in futures::future::join_all(handles).await {
+        match result {
+            Ok(county_reports) => reports.extend(county_reports),
+            Err(err) => {
+                tracing::error!(error = %err, "refresh task panicked");
+                reports.push(Report {
+                    county: "?".to_string(),
+                    part: "?".to_string(),
+                    error: Some(err.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    reports.sort_by(|a, b| (&a.county, &a.part).cmp(&(&b.county, &b.part)));
+
+    Ok(reports)
+}
+
+/// What to do when the database being generated already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum IfExists {
+    /// Refresh the existing data, re-fetching only what changed
+    Update,
+    /// Start over from an empty database
+    Replace,
+    /// Leave the existing file alone and fail
+    Refuse,
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Bring the database at `target` up to date with the ministry, re-fetching only counties whose
+/// published files changed since the last run. `county` restricts the refresh to a single county
+/// code, and `force` ignores what was recorded by previous runs.
+///
+/// The work happens on a temporary copy that is renamed over `target` only if every county
+/// succeeded, so readers never see a half-generated database.
+pub async fn do_year(
+    year: i32,
+    target: &Path,
+    if_exists: IfExists,
+    county: Option<String>,
+    force: bool,
+    opts: &FetchOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    if county.is_some() && if_exists == IfExists::Replace {
+        // starting over from an empty database would drop every other county
+        return Err(
+            "--county only refreshes one county, it cannot be combined with replace".into(),
+        );
+    }
+    let exists = target.exists();
+    if exists && if_exists == IfExists::Refuse {
+        return Err(format!("{} already exists", target.display()).into());
+    }
+
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp = temp_path(target);
+    if temp.exists() {
+        std::fs::remove_file(&temp)?;
+    }
+    if exists && if_exists == IfExists::Update {
+        db::snapshot(target, &temp).await?;
     }
-    futures::future::join_all(handles)
-        .await
-        .iter()
-        .for_each(|x| match x {
-            Err(err) => println!("{:#?}", err),
-            _ => {}
-        });
 
+    let fetcher = Arc::new(Fetcher::new(opts)?);
+    let db = db::create_pool(db::dsn(&temp)?.as_str(), true, db::DEFAULT_POOL_SIZE).await?;
+
+    let reports = refresh(&fetcher, &db, year, county.as_deref(), force).await;
     db.close().await;
+    let reports = match reports {
+        Ok(reports) => reports,
+        Err(err) => {
+            std::fs::remove_file(&temp)?;
+            return Err(err);
+        }
+    };
+
+    let failed = reports.iter().filter(|x| x.error.is_some()).count();
+    let changed = reports.iter().filter(|x| x.changed()).count();
+    print_summary(&reports);
+    tracing::info!(
+        year,
+        parts = reports.len(),
+        changed,
+        failed,
+        downloaded = fetcher.downloaded(),
+        duration_ms = started.elapsed().as_millis() as u64,
+        "finished"
+    );
 
+    if failed > 0 {
+        std::fs::remove_file(&temp)?;
+        return Err(format!(
+            "{failed} county refreshes failed for year {year}, {} left untouched",
+            target.display()
+        )
+        .into());
+    }
+
+    std::fs::rename(&temp, target)?;
+    tracing::info!(year, path = %target.display(), "written");
     Ok(())
 }
+
+/// Poll the ministry every `interval` and apply whatever was published since the last pass,
+/// until interrupted.
+///
+/// Each county part is replaced in place inside its own transaction, so a server reading the
+/// same database picks up the changes on its next query.
+pub async fn watch(
+    year: i32,
+    target: &Path,
+    county: Option<String>,
+    interval: Duration,
+    opts: &FetchOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let fetcher = Arc::new(Fetcher::new(opts)?);
+    let db = db::create_pool(db::dsn(target)?.as_str(), true, db::DEFAULT_POOL_SIZE).await?;
+
+    tracing::info!(year, path = %target.display(), interval = ?interval, "watching");
+    loop {
+        let started = Instant::now();
+        match refresh(&fetcher, &db, year, county.as_deref(), false).await {
+            Ok(reports) => {
+                let changed: Vec<String> = reports
+                    .iter()
+                    .filter(|x| x.changed())
+                    .map(|x| format!("{} {}", x.county, x.part))
+                    .collect();
+                let failed = reports.iter().filter(|x| x.error.is_some()).count();
+                if !changed.is_empty() {
+                    tracing::info!(year, changed = ?changed, "published changes applied");
+                }
+                tracing::info!(
+                    year,
+                    changed = changed.len(),
+                    failed,
+                    duration_ms = started.elapsed().as_millis() as u64,
+                    "poll finished"
+                );
+            }
+            Err(err) => tracing::error!(year, error = %err, "poll failed"),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    db.close().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::routing::{get, get_service};
+    use axum::Router;
+    use std::net::SocketAddr;
+    use tower_http::services::ServeDir;
+
+    #[tokio::test]
+    async fn failed_part_leaves_the_county_untouched() {
+        // the fixtures, except for a truncated candidates file in CV
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let app = Router::new()
+            .route(
+                "/admitere/2023/repartizare/CV/data/candidate.json",
+                get(|| async { "[{\"n\": " }),
+            )
+            .fallback(get_service(ServeDir::new(fixtures)).handle_error(
+                |err: std::io::Error| async move {
+                    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+                },
+            ));
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let fetcher = Arc::new(
+            Fetcher::new(&FetchOptions {
+                timeout: 10,
+                concurrency: 4,
+                retries: 0,
+                backoff: 0,
+                rate: 0.0,
+                admitere_url: format!("http://{addr}/admitere"),
+                evaluare_url: format!("http://{addr}/evaluare"),
+            })
+            .unwrap(),
+        );
+        let db = db::memory_pool().await.unwrap();
+
+        let reports = refresh(&fetcher, &db, 2023, None, false).await.unwrap();
+        for report in &reports {
+            assert_eq!(
+                report.error.is_some(),
+                report.county == "CV",
+                "{}",
+                report.part
+            );
+        }
+
+        let count = |table: &'static str, county: &'static str| {
+            let db = db.clone();
+            async move {
+                sqlx::query_scalar::<_, i64>(&format!(
+                    "SELECT COUNT(*) FROM {table} WHERE judet = ?"
+                ))
+                .bind(county)
+                .fetch_one(&db)
+                .await
+                .unwrap()
+            }
+        };
+        assert_eq!(count("specializari", "AB").await, 3);
+        assert_eq!(count("evaluare", "AB").await, 7);
+        assert_eq!(count("specializari", "CV").await, 0);
+        assert_eq!(count("evaluare", "CV").await, 0);
+    }
+}
@@ -0,0 +1,517 @@
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH, RETRY_AFTER};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::Router;
+use repartizare_api::{
+    Client, Contestatie, County, Error as ClientError, FullSchool, Participation, Placement, Round,
+    Status, Unplaced,
+};
+use repartizare_c8::config::{Limit, RateLimitConfig, ServerConfig};
+use repartizare_c8::dbmgr::DB;
+use repartizare_c8::fetch::FetchOptions;
+use repartizare_c8::server;
+use repartizare_c8::verify;
+use repartizare_c8::year_gen::{self, IfExists};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tower_http::services::ServeDir;
+
+const SCHOOL: &str = "Colegiul Național Horea, Cloșca și Crișan";
+
+/// Serve `tests/fixtures` the way the ministry sites lay out their files.
+fn serve_fixtures() -> SocketAddr {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let app =
+        Router::new().fallback(
+            axum::routing::get_service(ServeDir::new(fixtures)).handle_error(
+                |err: std::io::Error| async move {
+                    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+                },
+            ),
+        );
+
+    let server =
+        axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
+
+fn fetch_options(addr: SocketAddr) -> FetchOptions {
+    FetchOptions {
+        timeout: 10,
+        concurrency: 4,
+        retries: 0,
+        backoff: 0,
+        rate: 0.0,
+        admitere_url: format!("http://{addr}/admitere"),
+        evaluare_url: format!("http://{addr}/evaluare/"),
+    }
+}
+
+/// Percent-encode a single path segment.
+fn segment(val: &str) -> String {
+    let mut url = reqwest::Url::parse("http://localhost/").unwrap();
+    url.path_segments_mut().unwrap().push(val);
+    url.path()[1..].to_string()
+}
+
+async fn call(app: &Router, req: Request<Body>) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+    (status, headers, body.to_vec())
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, HeaderMap, Vec<u8>) {
+    call(app, Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+/// GET a route of the original API and unwrap its envelope.
+async fn get_v1<T: DeserializeOwned>(app: &Router, uri: &str) -> T {
+    let (status, _, body) = get(app, uri).await;
+    assert_eq!(status, StatusCode::OK, "{uri}");
+    serde_json::from_slice::<Status<T>>(&body)
+        .unwrap()
+        .into_result()
+        .unwrap()
+}
+
+async fn get_json(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let (status, _, body) = get(app, uri).await;
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+async fn graphql(app: &Router, query: &str) -> Value {
+    let (status, _, body) = call(
+        app,
+        Request::post("/api/v2/graphql")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "query": query }).to_string()))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// Generate 2023 from the fixtures into a temporary directory, and the server reading it.
+async fn setup() -> (tempfile::TempDir, ServerConfig, Router) {
+    let dir = tempfile::tempdir().unwrap();
+    let opts = fetch_options(serve_fixtures());
+    year_gen::do_year(
+        2023,
+        &dir.path().join("2023.db"),
+        IfExists::Replace,
+        None,
+        false,
+        &opts,
+    )
+    .await
+    .unwrap();
+
+    let config = ServerConfig {
+        data_dir: dir.path().to_path_buf(),
+        years: vec![2023],
+        ..Default::default()
+    };
+    let db = Arc::new(DB::new(
+        config.data_dir.clone(),
+        config.pool_size,
+        config.cache.entries,
+    ));
+    let app = server::app(&config, db).unwrap();
+    (dir, config, app)
+}
+
+#[tokio::test]
+async fn generated_year_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let opts = fetch_options(serve_fixtures());
+    let target = dir.path().join("2023.db");
+
+    year_gen::do_year(2023, &target, IfExists::Replace, None, false, &opts)
+        .await
+        .unwrap();
+    assert!(target.exists());
+    // AB6 has no admission average, which is a NULL and not an integrity problem
+    verify::verify_year(dir.path(), 2023, false, &opts)
+        .await
+        .unwrap();
+    // nothing changed upstream, so an update has nothing to re-fetch
+    year_gen::do_year(2023, &target, IfExists::Update, None, false, &opts)
+        .await
+        .unwrap();
+    assert!(
+        year_gen::do_year(2023, &target, IfExists::Refuse, None, false, &opts)
+            .await
+            .is_err()
+    );
+
+    // refreshing one county keeps the others
+    let cv = Some("CV".to_string());
+    year_gen::do_year(2023, &target, IfExists::Update, cv.clone(), true, &opts)
+        .await
+        .unwrap();
+    verify::verify_year(dir.path(), 2023, false, &opts)
+        .await
+        .unwrap();
+    assert!(
+        year_gen::do_year(2023, &target, IfExists::Replace, cv, false, &opts)
+            .await
+            .is_err()
+    );
+    verify::verify_year(dir.path(), 2023, false, &opts)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn original_api_serves_the_year() {
+    let (dir, config, app) = setup().await;
+
+    let years: Vec<i32> = get_v1(&app, "/adm_api/years").await;
+    assert_eq!(years, vec![2023]);
+
+    let counties: Vec<County> = get_v1(&app, "/adm_api/2023/counties").await;
+    let mut codes: Vec<_> = counties.iter().map(|x| x.code.as_str()).collect();
+    codes.sort_unstable();
+    assert_eq!(codes, ["AB", "CV"]);
+    assert!(counties
+        .iter()
+        .any(|x| x.id == Some(14) && x.name == "Covasna"));
+
+    let mut schools: Vec<String> = get_v1(&app, "/adm_api/2023/AB/schools?round=1").await;
+    schools.sort_unstable();
+    assert_eq!(schools, [SCHOOL, "Liceul Tehnologic Alba Iulia"]);
+
+    let school: FullSchool = get_v1(
+        &app,
+        &format!("/adm_api/2023/AB/fullSchool/{}?round=1", segment(SCHOOL)),
+    )
+    .await;
+    assert_eq!(school.specializari.len(), 2);
+    let mate_info = &school.specializari[&101];
+    assert_eq!(mate_info.spec.ultima_medie, Some(9.12));
+    let mut placed: Vec<_> = mate_info.elevi.iter().map(|x| x.id.as_str()).collect();
+    placed.sort_unstable();
+    assert_eq!(placed, ["AB1", "AB2"]);
+    let filologie = &school.specializari[&102];
+    assert!(filologie.spec.bilingv);
+    assert_eq!(filologie.elevi.len(), 1);
+
+    let unplaced: Unplaced = get_v1(&app, "/adm_api/2023/AB/unplaced?round=1").await;
+    let mut ids: Vec<_> = unplaced.elevi.iter().map(|x| x.id.as_str()).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, ["AB5", "AB6"]);
+    assert!(unplaced
+        .elevi
+        .iter()
+        .all(|x| x.status == Placement::Unplaced && x.id_specializare.is_none()));
+
+    // both renderings of a missing average, side by side on the same server
+    let (_, body) = get_json(&app, "/adm_api/2023/AB/unplaced?round=1&sentinels=legacy").await;
+    let ab6 = |body: &Value| {
+        body["data"]["elevi"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|x| x["id"] == "AB6")
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(ab6(&body)["medie_adm"], -1.0);
+    let (_, body) = get_json(&app, "/adm_api/2023/AB/unplaced?round=1").await;
+    assert_eq!(ab6(&body)["medie_adm"], Value::Null);
+
+    let participation: Participation = get_v1(&app, "/adm_api/2023/AB/participation").await;
+    assert_eq!(participation.inscrisi, 7);
+    assert_eq!(participation.prezenti, 6);
+    assert_eq!(participation.absenti, 1);
+    assert_eq!(participation.contestatii, 1);
+    assert_eq!(participation.admitere, 6);
+
+    let appeals: Vec<Contestatie> = get_v1(&app, "/adm_api/2023/CV/appeals").await;
+    assert_eq!(appeals.len(), 1);
+    assert_eq!(appeals[0].id, "CV2");
+    assert_eq!(appeals[0].nota_mate_initiala, Some(8.3));
+    assert_eq!(appeals[0].nota_mate, Some(8.9));
+    // 8.59 before the appeal and 8.75 after, behind CV1's 9.05 either way
+    assert_eq!(appeals[0].loc_initial, Some(2));
+    assert_eq!(appeals[0].loc, Some(2));
+
+    for round in ["9", "abc"] {
+        let uri = format!("/adm_api/2023/AB/schools?round={round}");
+        let (status, body) = get_json(&app, &uri).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert_eq!(body["type"], "error", "{uri}");
+    }
+
+    // conditional requests
+    let (status, headers, _) = get(&app, "/adm_api/2023/counties").await;
+    assert_eq!(status, StatusCode::OK);
+    let etag = headers.get(ETAG).expect("counties has an ETag").clone();
+    let (status, _, body) = call(
+        &app,
+        Request::get("/adm_api/2023/counties")
+            .header(IF_NONE_MATCH, etag.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert!(body.is_empty());
+
+    // another replica with a copy of the same file agrees on the ETag
+    let replica = tempfile::tempdir().unwrap();
+    std::fs::copy(dir.path().join("2023.db"), replica.path().join("2023.db")).unwrap();
+    let replica = server::app(
+        &ServerConfig {
+            data_dir: replica.path().to_path_buf(),
+            ..config
+        },
+        Arc::new(DB::new(replica.path().to_path_buf(), 1, 0)),
+    )
+    .unwrap();
+    let (_, headers, _) = get(&replica, "/adm_api/2023/counties").await;
+    assert_eq!(headers.get(ETAG), Some(&etag));
+}
+
+#[tokio::test]
+async fn client_reads_the_original_api() {
+    let (_dir, config, app) = setup().await;
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+    let url = format!("http://{}{}", server.local_addr(), config.base_path());
+    tokio::spawn(server);
+    let client = Client::new(url.parse().unwrap());
+
+    assert_eq!(client.years().await.unwrap(), vec![2023]);
+    let counties = client.counties(2023).await.unwrap();
+    assert!(counties.iter().any(|x| x.code == "AB"));
+
+    let mut schools = client.schools(2023, "AB", Round::First).await.unwrap();
+    schools.sort_unstable();
+    assert_eq!(schools, [SCHOOL, "Liceul Tehnologic Alba Iulia"]);
+    let school = client
+        .full_school(2023, "AB", SCHOOL, Round::First)
+        .await
+        .unwrap();
+    assert_eq!(school.specializari.len(), 2);
+
+    client.unplaced(2023, "AB", Round::First).await.unwrap();
+    client.participation(2023, "AB").await.unwrap();
+    client.appeals(2023, "AB").await.unwrap();
+
+    let err = client.counties(2019).await.unwrap_err();
+    assert!(matches!(err, ClientError::Api(_)), "{err}");
+}
+
+#[tokio::test]
+async fn openapi_documents_both_versions() {
+    let (_dir, _, app) = setup().await;
+
+    let (status, body) = get_json(&app, "/adm_api/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["paths"]
+        .as_object()
+        .unwrap()
+        .contains_key("/{year}/counties"));
+    assert_eq!(body["servers"][0]["url"], "/adm_api");
+
+    let (status, body) = get_json(&app, "/api/v2/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["paths"]
+        .as_object()
+        .unwrap()
+        .contains_key("/{year}/counties/{county}/appeals"));
+    assert_eq!(body["servers"][0]["url"], "/api/v2");
+
+    let (status, _, _) = get(&app, "/adm_api/docs").await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    let (status, _, body) = get(&app, "/adm_api/docs/swagger-initializer.js").await;
+    assert_eq!(status, StatusCode::OK);
+    let initializer = String::from_utf8(body).unwrap();
+    assert!(initializer.contains("/adm_api/openapi.json"));
+    assert!(initializer.contains("/api/v2/openapi.json"));
+}
+
+#[tokio::test]
+async fn second_version_serves_the_year() {
+    let (dir, config, app) = setup().await;
+
+    let (status, body) = get_json(&app, "/api/v2/years").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"], json!([2023]));
+
+    let (status, body) = get_json(&app, "/api/v2/2023/counties").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let (status, body) = get_json(&app, "/api/v2/2023/counties/CV/schools").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let (status, body) = get_json(
+        &app,
+        &format!("/api/v2/2023/counties/AB/schools/{}", segment(SCHOOL)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let programs = body["data"]["programs"].as_array().unwrap();
+    assert_eq!(programs.len(), 2);
+    assert!(programs
+        .iter()
+        .any(|x| x["id"] == 102 && x["bilingual"] == true));
+
+    let (status, body) = get_json(&app, "/api/v2/2023/counties/CV/unplaced").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["candidates"][0]["id"], "CV4");
+
+    let (status, body) = get_json(&app, "/api/v2/2023/counties/CV/participation").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["registered"], 4);
+    assert_eq!(body["data"]["appealed"], 1);
+    assert_eq!(body["data"]["admitted"], 4);
+
+    let (status, body) = get_json(&app, "/api/v2/2023/counties/AB/appeals").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["candidate_id"], "AB3");
+    assert_eq!(body["data"][0]["grades"]["romanian"]["after_appeal"], 8.5);
+    // 8.20 before the appeal and 8.40 after, behind AB1 and AB2 either way
+    assert_eq!(
+        body["data"][0]["rank"],
+        json!({ "initial": 3, "after_appeal": 3 })
+    );
+
+    let (status, body) = get_json(&app, "/api/v2/2023/counties/AB/schools?round=abc").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+
+    let (status, body) = get_json(&app, "/api/v2/2019/counties").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "unknown_year");
+
+    let (status, body) = get_json(&app, "/api/v2/nowhere").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+
+    // one request per client, then errors in the format of the API that was called
+    let limited = ServerConfig {
+        rate_limit: RateLimitConfig {
+            enabled: true,
+            default: Limit {
+                per_second: 0.01,
+                burst: 1,
+            },
+            ..Default::default()
+        },
+        ..config
+    };
+    let db = Arc::new(DB::new(dir.path().to_path_buf(), 1, 0));
+    let limited = server::app(&limited, db).unwrap();
+    let from_client = |uri: &str| {
+        let mut req = Request::get(uri).body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([198, 51, 100, 1], 4000))));
+        req
+    };
+    let (status, _, _) = call(&limited, from_client("/api/v2/2023/counties")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, headers, body) = call(&limited, from_client("/api/v2/2023/counties")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(headers.contains_key(RETRY_AFTER));
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["code"], "rate_limited");
+    let (status, _, body) = call(&limited, from_client("/adm_api/2023/counties")).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["type"], "error");
+}
+
+#[tokio::test]
+async fn graphql_serves_the_year() {
+    let (_dir, _, app) = setup().await;
+
+    let body = graphql(
+        &app,
+        r#"{
+            year(year: 2023) {
+                county(code: "AB") {
+                    school(name: "Liceul Tehnologic Alba Iulia") {
+                        specializations { id students { id } }
+                    }
+                }
+            }
+        }"#,
+    )
+    .await;
+    assert_eq!(
+        body["data"]["year"]["county"]["school"]["specializations"],
+        json!([{ "id": 201, "students": [{ "id": "AB4" }] }])
+    );
+
+    let body = graphql(
+        &app,
+        r#"{ year(year: 2023) { counties(codes: ["AB"]) { schools { name } } } }"#,
+    )
+    .await;
+    assert!(body.get("errors").is_none(), "{body}");
+
+    // every student of every year stays out of reach of a single query
+    let body = graphql(
+        &app,
+        "{ years { counties { schools { specializations { students { id } } } } } }",
+    )
+    .await;
+    assert_eq!(body["data"], Value::Null, "{body}");
+    assert!(
+        body["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("too complex"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn operations_endpoints() {
+    let (dir, config, app) = setup().await;
+
+    let (status, body) = get_json(&app, "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"], "ok");
+
+    let (status, body) = get_json(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["years"][0]["year"], 2023);
+
+    let missing = ServerConfig {
+        years: vec![2019, 2023],
+        ..config.clone()
+    };
+    let db = Arc::new(DB::new(dir.path().to_path_buf(), 1, 1));
+    let unready = server::app(&missing, db).unwrap();
+    let (status, body) = get_json(&unready, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["ready"], false);
+    assert_eq!(body["years"][0]["ready"], false);
+
+    // a data route has to be served before its label shows up
+    let _: Vec<County> = get_v1(&app, "/adm_api/2023/counties").await;
+    let (status, _, body) = get(&app, "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    let metrics = String::from_utf8(body).unwrap();
+    assert!(metrics.contains("http_requests_total"));
+    assert!(metrics.contains(":year/counties"));
+
+    let (status, _, _) = get(&app, "/nowhere").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}